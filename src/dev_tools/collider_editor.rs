@@ -0,0 +1,573 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::input::{ActionState, GameAction, TypingText};
+use crate::resources::{DevMode, GameState};
+use crate::PIXEL_SCALE;
+
+use super::{cursor_position, spawn_panel, BUTTON_COLOR, SELECTED_BUTTON_COLOR, TEXT_COLOR};
+use super::history::{Edit, EditHistory};
+use super::interactable_editor::InteractableEditor;
+
+use crate::game::collider_merge::merge_collider_rects;
+use crate::game::rooms::{self, ColliderEdit, Room};
+use crate::game::script::{self, ActionCommand};
+use crate::game::svg::{self, ColliderSvg, SvgRect};
+use crate::game::ColliderType;
+
+
+///Every ColliderType the palette offers, in the order the buttons are shown
+const PALETTE: [(ColliderType, &str); 3] = [
+    (ColliderType::RIGID, "Wall"),
+    (ColliderType::ChangeRoom, "Exit"),
+    (ColliderType::Interactable, "Interactable"),
+];
+
+pub fn collider_editor_plugin(app: &mut App) {
+    app.add_systems(Update, (
+        toggle_collider_editor,
+        pick_brush.run_if(resource_exists::<ColliderEditor>),
+        type_exit_destination.run_if(resource_exists::<ColliderEditor>),
+        paint_colliders.run_if(resource_exists::<ColliderEditor>),
+        save_colliders.run_if(resource_exists::<ColliderEditor>),
+        draw_collider_editor.run_if(resource_exists::<ColliderEditor>),
+    ).chain().run_if(in_state(GameState::Running).and_then(resource_exists_and_equals(DevMode(true)))))
+
+    .add_systems(OnExit(GameState::Running), close_collider_editor);
+}
+
+///Exists while collider painting is on, left click paints the brush onto a tile and right click erases it
+#[derive(Resource, Debug)]
+pub(super) struct ColliderEditor {
+    brush: ColliderType,
+
+    //where the Exit brush leads, as the data-* attributes it gives the tiles it paints
+    exit: Option<BTreeMap<String, String>>,
+
+    //the destination being typed into the palette, like `L1 _8_9 3 4`
+    exit_field: Option<String>,
+
+    //rooms the cursor has been over, by room identifier
+    rooms: HashMap<String, EditedRoom>,
+
+    //how the rooms painted in since the mouse went down looked before it, so the stroke can be undone in one go
+    stroke: HashMap<String, ColliderSvg>,
+}
+
+impl ColliderEditor {
+    ///Puts a room's colliders back to `collider_svg`, as when an edit is undone
+    pub(super) fn replace_room(&mut self, room: &str, path: PathBuf, collider_svg: &ColliderSvg) {
+        let mut edited = EditedRoom::from_svg(path, collider_svg);
+        edited.unsaved = true;
+        self.rooms.insert(room.to_string(), edited);
+    }
+}
+
+///The collider file of one room, cut into single tiles so they can be painted one at a time
+#[derive(Debug)]
+struct EditedRoom {
+    path: PathBuf,
+    width: f32,
+    height: f32,
+    tiles: BTreeMap<(i32, i32), SvgRect>,
+    unsaved: bool,
+}
+
+impl EditedRoom {
+    fn from_svg(path: PathBuf, collider_svg: &ColliderSvg) -> Self {
+        let mut tiles = BTreeMap::new();
+        for rect in &collider_svg.rects {
+            let (x, y) = (rect.x.floor() as i32, rect.y.floor() as i32);
+            for ty in y..y + rect.height.ceil().max(1.0) as i32 {
+                for tx in x..x + rect.width.ceil().max(1.0) as i32 {
+                    let tile = SvgRect { x: tx as f32, y: ty as f32, width: 1.0, height: 1.0, ..rect.clone() };
+                    tiles.insert((tx, ty), tile);
+                }
+            }
+        }
+
+        EditedRoom { path, width: collider_svg.width, height: collider_svg.height, tiles, unsaved: false }
+    }
+
+    fn to_svg(&self) -> ColliderSvg {
+        ColliderSvg {
+            width: self.width,
+            height: self.height,
+            rects: self.tiles.values().cloned().collect(),
+        }
+    }
+
+    ///The svg as it is written to disk, with the tiles merged back into as few rects as they fit in
+    fn to_merged_svg(&self) -> ColliderSvg {
+        let typed_rects: Vec<(ColliderType, SvgRect)> = self
+            .tiles
+            .values()
+            .map(|rect| (rooms::collider_type_for(&rect.fill), rect.clone()))
+            .collect();
+        let (merged_rects, _) = merge_collider_rects(&typed_rects);
+
+        ColliderSvg {
+            width: self.width,
+            height: self.height,
+            rects: merged_rects.into_iter().map(|(_, rect)| rect).collect(),
+        }
+    }
+
+    ///Paints a tile with a type and its data-* attributes, or erases it with None, false when that did not change anything
+    fn paint(&mut self, tile: (i32, i32), brush: Option<(ColliderType, &BTreeMap<String, String>)>) -> bool {
+        match brush {
+            Some((style, data)) => {
+                //painting the same type again keeps what the tile already had, unless the brush leads somewhere else
+                if self
+                    .tiles
+                    .get(&tile)
+                    .is_some_and(|rect| rooms::collider_type_for(&rect.fill) == style && (data.is_empty() || rect.data == *data))
+                {
+                    return false;
+                }
+                self.tiles.insert(tile, SvgRect {
+                    x: tile.0 as f32,
+                    y: tile.1 as f32,
+                    width: 1.0,
+                    height: 1.0,
+                    fill: rooms::collider_fill(style).to_string(),
+                    data: data.clone(),
+                });
+            }
+            None => {
+                if self.tiles.remove(&tile).is_none() {
+                    return false;
+                }
+            }
+        }
+        self.unsaved = true;
+        true
+    }
+}
+
+//Tag components for the palette
+#[derive(Component)]
+struct ColliderPalette;
+
+#[derive(Component)]
+struct BrushButton(ColliderType);
+
+#[derive(Component)]
+struct ExitField;
+
+#[derive(Component)]
+struct ExitFieldText;
+
+fn toggle_collider_editor(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    editor: Option<Res<ColliderEditor>>,
+    interactable_editor: Option<Res<InteractableEditor>>,
+    palettes: Query<Entity, With<ColliderPalette>>,
+) {
+    if !actions.just_pressed(GameAction::DevColliderEditor) {
+        return;
+    }
+
+    if let Some(editor) = editor {
+        if editor.rooms.values().any(|room| room.unsaved) {
+            warn!("Closed the collider editor with unsaved rooms, the edits only last until the level reloads");
+        }
+        for entity in &palettes {
+            commands.entity(entity).despawn_recursive();
+        }
+        commands.remove_resource::<ColliderEditor>();
+        return;
+    }
+
+    if interactable_editor.is_some() {
+        warn!("Close the interactable editor before painting colliders");
+        return;
+    }
+
+    info!("Collider editor on: left click paints, right click erases");
+    commands.insert_resource(ColliderEditor {
+        brush: ColliderType::RIGID,
+        exit: None,
+        exit_field: None,
+        rooms: HashMap::new(),
+        stroke: HashMap::new(),
+    });
+
+    let style = Style { right: Val::Px(10.0), row_gap: Val::Px(6.0), ..default() };
+    spawn_panel(&mut commands, style, ColliderPalette)
+        .with_children(|parent| {
+            for (style, label) in PALETTE {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                                column_gap: Val::Px(8.0),
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                        BrushButton(style),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(NodeBundle {
+                            style: Style { width: Val::Px(14.0), height: Val::Px(14.0), ..default() },
+                            background_color: tile_color(style).into(),
+                            ..default()
+                        });
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle { font_size: 20.0, color: TEXT_COLOR, ..default() },
+                        ));
+                    });
+            }
+
+            //click to type where the Exit brush leads
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style { padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)), ..default() },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                    ExitField,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section("", TextStyle { font_size: 16.0, color: TEXT_COLOR, ..default() }),
+                        ExitFieldText,
+                    ));
+                });
+        });
+}
+
+fn pick_brush(
+    mut editor: ResMut<ColliderEditor>,
+    mut buttons: Query<(&Interaction, &BrushButton, &mut BackgroundColor)>,
+) {
+    for (interaction, button, _) in &buttons {
+        if *interaction == Interaction::Pressed {
+            editor.brush = button.0;
+        }
+    }
+
+    for (_, button, mut color) in &mut buttons {
+        let wanted = if button.0 == editor.brush { SELECTED_BUTTON_COLOR } else { BUTTON_COLOR };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+}
+
+fn type_exit_destination(
+    mut commands: Commands,
+    mut editor: ResMut<ColliderEditor>,
+    mut keys: EventReader<KeyboardInput>,
+    mut fields: Query<(Ref<Interaction>, &mut BackgroundColor), With<ExitField>>,
+    mut texts: Query<&mut Text, With<ExitFieldText>>,
+) {
+    for (interaction, _) in &fields {
+        if interaction.is_changed() && *interaction == Interaction::Pressed && editor.exit_field.is_none() {
+            editor.exit_field = Some(String::new());
+            commands.insert_resource(TypingText);
+            keys.clear();
+        }
+    }
+
+    if let Some(mut field) = editor.exit_field.take() {
+        //Some(true) once Enter is pressed, Some(false) for Escape
+        let mut finished = None;
+        for key in keys.read().filter(|key| key.state == ButtonState::Pressed) {
+            match &key.logical_key {
+                Key::Enter => finished = Some(true),
+                Key::Escape => finished = Some(false),
+                Key::Backspace => {
+                    field.pop();
+                }
+                Key::Space => field.push(' '),
+                Key::Character(text) => field.push_str(text),
+                _ => {}
+            }
+        }
+
+        match finished {
+            None => editor.exit_field = Some(field),
+            Some(true) => match exit_data(&field) {
+                Ok(data) => {
+                    editor.exit = Some(data);
+                    editor.brush = ColliderType::ChangeRoom;
+                }
+                Err(message) => {
+                    //keep typing so it can be fixed
+                    warn!("{}", message);
+                    editor.exit_field = Some(field);
+                }
+            },
+            Some(false) => {}
+        }
+        if editor.exit_field.is_none() {
+            commands.remove_resource::<TypingText>();
+        }
+    } else {
+        keys.clear();
+    }
+
+    let wanted = match (&editor.exit_field, &editor.exit) {
+        (Some(field), _) => format!("Exits lead to: {}_", field),
+        (None, Some(exit)) => format!("Exits lead to: L{} {} {}", exit["level"], exit["room"], exit["spawn"]),
+        (None, None) => "Click to set where exits lead".to_string(),
+    };
+    for mut text in &mut texts {
+        if text.sections[0].value != wanted {
+            text.sections[0].value = wanted.clone();
+        }
+    }
+
+    let wanted = if editor.exit_field.is_some() { SELECTED_BUTTON_COLOR } else { BUTTON_COLOR };
+    for (_, mut color) in &mut fields {
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+}
+
+///Reads a destination typed like the teleport action, `L1 _8_9 3 4`, into the data-* attributes of an exit
+fn exit_data(text: &str) -> Result<BTreeMap<String, String>, String> {
+    match script::parse_action(&format!("teleport {text}"))? {
+        ActionCommand::Teleport(destination) => Ok(rooms::exit_data(&destination)),
+        _ => unreachable!("teleport always parses into a Teleport"),
+    }
+}
+
+///The room under the cursor and the tile in it, its collider file is read the first time the cursor gets there
+fn hovered_tile<'a>(
+    editor: &'a mut ColliderEditor,
+    rooms: &'a Query<&Room>,
+    cursor: Vec2,
+) -> Option<(&'a str, &'a mut EditedRoom, (i32, i32))> {
+    let (room, tile) = rooms.iter().find_map(|room| Some((room, room.tile_at(cursor)?)))?;
+
+    if !editor.rooms.contains_key(&room.identifier) {
+        if room.collider_path.as_os_str().is_empty() {
+            return None;
+        }
+        match svg::load_collider_svg(&room.collider_path) {
+            Ok(collider_svg) => {
+                let edited = EditedRoom::from_svg(room.collider_path.clone(), &collider_svg);
+                editor.rooms.insert(room.identifier.clone(), edited);
+            }
+            Err(err) => {
+                error!("Can not edit the colliders of room {}: {}", room.identifier, err);
+                return None;
+            }
+        }
+    }
+
+    let edited = editor.rooms.get_mut(&room.identifier)?;
+    Some((room.identifier.as_str(), edited, tile))
+}
+
+fn paint_colliders(
+    mut editor: ResMut<ColliderEditor>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    (windows, cameras): (Query<&Window, With<PrimaryWindow>>, Query<(&Camera, &GlobalTransform)>),
+    rooms: Query<&Room>,
+    palette: Query<&Interaction, Or<(With<ColliderPalette>, With<BrushButton>, With<ExitField>)>>,
+    mut edits: EventWriter<ColliderEdit>,
+    mut history: ResMut<EditHistory>,
+) {
+    let brush = if mouse.pressed(MouseButton::Left) {
+        Some(editor.brush)
+    } else if mouse.pressed(MouseButton::Right) {
+        None
+    } else {
+        //the stroke is over, remember each room it changed
+        let stroke = std::mem::take(&mut editor.stroke);
+        for (identifier, before) in stroke {
+            let Some(room) = editor.rooms.get(&identifier) else {
+                continue;
+            };
+            let after = room.to_svg();
+            if after != before {
+                history.push(Edit::Colliders { room: identifier, path: room.path.clone(), before, after });
+            }
+        }
+        return;
+    };
+
+    //the buttons block the cursor from the panel behind them, so both have to be asked
+    if palette.iter().any(|interaction| *interaction != Interaction::None) {
+        return;
+    }
+
+    let data = match (brush, &editor.exit) {
+        (Some(ColliderType::ChangeRoom), None) => {
+            if mouse.just_pressed(MouseButton::Left) {
+                warn!("Set where exits lead in the palette before painting them");
+            }
+            return;
+        }
+        (Some(ColliderType::ChangeRoom), Some(exit)) => exit.clone(),
+        _ => BTreeMap::new(),
+    };
+
+    let Some(cursor) = cursor_position(&windows, &cameras) else {
+        return;
+    };
+
+    let mut stroke = std::mem::take(&mut editor.stroke);
+    if let Some((identifier, room, tile)) = hovered_tile(&mut editor, &rooms, cursor) {
+        let before = (!stroke.contains_key(identifier)).then(|| room.to_svg());
+        if room.paint(tile, brush.map(|style| (style, &data))) {
+            edits.send(ColliderEdit { room: identifier.to_string(), collider_svg: room.to_svg() });
+            if let Some(before) = before {
+                stroke.insert(identifier.to_string(), before);
+            }
+        }
+    }
+    editor.stroke = stroke;
+}
+
+fn save_colliders(mut editor: ResMut<ColliderEditor>, actions: Res<ActionState>) {
+    if !actions.just_pressed(GameAction::DevSaveColliders) {
+        return;
+    }
+
+    for (identifier, room) in editor.rooms.iter_mut().filter(|(_, room)| room.unsaved) {
+        match svg::write_collider_svg(&room.path, &room.to_merged_svg()) {
+            Ok(()) => {
+                room.unsaved = false;
+                info!("Saved the colliders of room {} to {}", identifier, room.path.display());
+            }
+            Err(err) => error!("Could not save the colliders of room {}: {}", identifier, err),
+        }
+    }
+}
+
+fn draw_collider_editor(
+    editor: Res<ColliderEditor>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    rooms: Query<&Room>,
+    mut gizmos: Gizmos,
+) {
+    let tile_size = Vec2::splat(PIXEL_SCALE);
+
+    for room in &rooms {
+        let Some(edited) = editor.rooms.get(&room.identifier) else {
+            continue;
+        };
+        for ((x, y), rect) in &edited.tiles {
+            let center = room.tile_position(*x as f32, *y as f32) + tile_size / 2.0;
+            gizmos.rect_2d(center, 0.0, tile_size * 0.9, tile_color(rooms::collider_type_for(&rect.fill)));
+        }
+    }
+
+    //outline the tile the brush would land on
+    let Some(cursor) = cursor_position(&windows, &cameras) else {
+        return;
+    };
+    if let Some((room, (x, y))) = rooms.iter().find_map(|room| Some((room, room.tile_at(cursor)?))) {
+        let center = room.tile_position(x as f32, y as f32) + tile_size / 2.0;
+        gizmos.rect_2d(center, 0.0, tile_size, tile_color(editor.brush).with_alpha(1.0));
+    }
+}
+
+fn tile_color(style: ColliderType) -> Color {
+    match style {
+        ColliderType::RIGID => Color::srgba(1.0, 0.2, 0.2, 0.8),
+        ColliderType::ChangeRoom => Color::srgba(0.2, 1.0, 0.2, 0.8),
+        ColliderType::Interactable => Color::srgba(0.3, 0.5, 1.0, 0.8),
+    }
+}
+
+fn close_collider_editor(mut commands: Commands, palettes: Query<Entity, With<ColliderPalette>>) {
+    for entity in &palettes {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<ColliderEditor>();
+    commands.remove_resource::<TypingText>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room_with(rects: Vec<SvgRect>) -> EditedRoom {
+        EditedRoom::from_svg(PathBuf::new(), &ColliderSvg { width: 4.0, height: 4.0, rects })
+    }
+
+    fn rect(x: f32, y: f32, width: f32, fill: &str) -> SvgRect {
+        SvgRect { x, y, width, height: 1.0, fill: fill.to_string(), data: BTreeMap::new() }
+    }
+
+    #[test]
+    fn wide_rects_are_cut_into_tiles() {
+        let mut exit = rect(1.0, 2.0, 2.0, "#00FF00");
+        exit.data.insert("room".to_string(), "_8_9".to_string());
+        let room = room_with(vec![exit]);
+
+        assert_eq!(room.tiles.len(), 2);
+        assert_eq!(room.tiles[&(2, 2)].data["room"], "_8_9");
+        assert_eq!(room.tiles[&(2, 2)].width, 1.0);
+    }
+
+    #[test]
+    fn painting_and_erasing_tiles() {
+        let mut room = room_with(vec![rect(0.0, 0.0, 1.0, "#000000")]);
+        let none = BTreeMap::new();
+
+        assert!(!room.paint((0, 0), Some((ColliderType::RIGID, &none))));
+        assert!(!room.unsaved);
+
+        let exit = exit_data("L1 _8_9 3 4").unwrap();
+        assert!(room.paint((0, 0), Some((ColliderType::ChangeRoom, &exit))));
+        assert_eq!(room.tiles[&(0, 0)].fill, "#00FF00");
+        assert_eq!(room.tiles[&(0, 0)].data, exit);
+        assert!(!room.paint((0, 0), Some((ColliderType::ChangeRoom, &exit))));
+
+        //painting another destination over an exit moves where it leads
+        let other_exit = exit_data("2 _0_0 1 1").unwrap();
+        assert!(room.paint((0, 0), Some((ColliderType::ChangeRoom, &other_exit))));
+        assert_eq!(room.tiles[&(0, 0)].data["level"], "2");
+
+        assert!(room.paint((3, 1), Some((ColliderType::RIGID, &none))));
+        assert!(room.paint((0, 0), None));
+        assert!(!room.paint((0, 0), None));
+
+        assert_eq!(room.to_svg().rects, vec![rect(3.0, 1.0, 1.0, "#000000")]);
+        assert!(room.unsaved);
+    }
+
+    #[test]
+    fn saved_tiles_are_merged() {
+        let mut exit = rect(0.0, 1.0, 2.0, "#00FF00");
+        exit.data.insert("room".to_string(), "_8_9".to_string());
+        let room = room_with(vec![rect(0.0, 0.0, 3.0, "#000000"), exit.clone()]);
+        assert_eq!(room.to_svg().rects.len(), 5);
+
+        let saved = room.to_merged_svg();
+        assert_eq!(saved.rects.len(), 2);
+        assert!(saved.rects.contains(&rect(0.0, 0.0, 3.0, "#000000")));
+        assert!(saved.rects.contains(&exit));
+    }
+
+    #[test]
+    fn exit_destinations_are_typed_like_teleports() {
+        let exit = exit_data("L1 _8_9 3 4").unwrap();
+        assert_eq!(exit["level"], "1");
+        assert_eq!(exit["room"], "_8_9");
+        assert_eq!(exit["spawn"], "3 4");
+
+        assert!(exit_data("L1 _8_9").is_err());
+        assert!(exit_data("L1 _8_9 three 4").is_err());
+    }
+}
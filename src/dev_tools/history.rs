@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::game::interactable_sprite;
+use crate::game::interaction::{self, Interactable};
+use crate::game::rooms::{ColliderEdit, RoomId};
+use crate::game::script::InteractableScript;
+use crate::game::svg::ColliderSvg;
+use crate::game::world_state::WorldState;
+use crate::input::TypingText;
+use crate::resources::{DevMode, GameState};
+
+use super::collider_editor::ColliderEditor;
+
+///How many edits can be undone, the oldest are forgotten past this
+const HISTORY_LENGTH: usize = 256;
+
+pub fn history_plugin(app: &mut App) {
+    app
+        .init_resource::<EditHistory>()
+        .add_systems(Update, undo_redo.run_if(
+            in_state(GameState::Running).and_then(resource_exists_and_equals(DevMode(true)))
+        ))
+
+        //the entities the edits point at are gone with the old level
+        .add_systems(OnEnter(GameState::LevelLoading), clear_history);
+}
+
+///An interactable as it was at one point, along with the room it gets saved with
+#[derive(Debug, Clone)]
+pub(super) struct Placed {
+    pub interactable: Box<Interactable>,
+    pub room: Option<String>,
+}
+
+impl Placed {
+    pub fn new(interactable: &Interactable, room: Option<&RoomId>) -> Self {
+        Placed {
+            interactable: Box::new(interactable.clone()),
+            room: room.map(|room| room.0.clone()),
+        }
+    }
+}
+
+///One change made with the dev tools, holding what things looked like before and after it so it can go either way
+#[derive(Debug, Clone)]
+pub(super) enum Edit {
+    //an interactable was placed (nothing `before`), deleted (nothing `after`), moved, resized or had its properties changed
+    Interactable {
+        entity: Entity,
+        before: Option<Placed>,
+        after: Option<Placed>,
+    },
+
+    //one stroke of the collider painter over a room
+    Colliders {
+        room: String,
+        path: PathBuf,
+        before: ColliderSvg,
+        after: ColliderSvg,
+    },
+}
+
+impl Edit {
+    fn describe(&self) -> String {
+        match self {
+            Edit::Interactable { before, after, .. } => {
+                let id = after.as_ref().or(before.as_ref()).map(|placed| placed.interactable.id.as_str()).unwrap_or_default();
+                match (before, after) {
+                    (None, _) => format!("placing interactable {id:?}"),
+                    (_, None) => format!("deleting interactable {id:?}"),
+                    _ => format!("changing interactable {id:?}"),
+                }
+            }
+            Edit::Colliders { room, .. } => format!("painting colliders in room {room}"),
+        }
+    }
+
+    ///Puts things back to how they were before the edit, or how they were after it when redoing
+    /// gives the new entity when an interactable had to be spawned again
+    fn apply(&self, targets: &mut EditTargets, undo: bool) -> Result<Option<Entity>, String> {
+        match self {
+            Edit::Interactable { entity, before, after } => {
+                let (from, to) = if undo { (after, before) } else { (before, after) };
+
+                match (to, targets.interactables.get_mut(*entity)) {
+                    (None, Ok(_)) => {
+                        targets.commands.entity(*entity).despawn_recursive();
+                        Ok(None)
+                    }
+                    (None, Err(_)) => Ok(None),
+                    (Some(placed), Ok((mut interactable, room_id))) => {
+                        let (restored, script) = with_script(placed);
+                        if interactable.action != restored.action {
+                            targets.commands.entity(*entity).insert(script);
+                        }
+                        *interactable = restored;
+
+                        if room_id.map(|room_id| &room_id.0) != placed.room.as_ref() {
+                            match &placed.room {
+                                Some(room) => targets.commands.entity(*entity).insert(RoomId(room.clone())),
+                                None => targets.commands.entity(*entity).remove::<RoomId>(),
+                            };
+                        }
+                        Ok(None)
+                    }
+                    (Some(placed), Err(_)) if from.is_none() => Ok(Some(targets.spawn(placed))),
+                    (Some(placed), Err(_)) => Err(format!("interactable {:?} is not loaded any more", placed.interactable.id)),
+                }
+            }
+            Edit::Colliders { room, path, before, after } => {
+                let collider_svg = if undo { before } else { after };
+
+                let Some(editor) = targets.collider_editor.as_mut() else {
+                    return Err("the collider editor is closed".to_string());
+                };
+                editor.replace_room(room, path.clone(), collider_svg);
+                targets.collider_edits.send(ColliderEdit { room: room.clone(), collider_svg: collider_svg.clone() });
+                Ok(None)
+            }
+        }
+    }
+}
+
+///The interactable as it was placed, with its actions parsed (and any bad ones logged)
+fn with_script(placed: &Placed) -> (Interactable, InteractableScript) {
+    let source = format!("interactable {:?}", placed.interactable.id);
+    interaction::with_scripts(source, vec![*placed.interactable.clone()])
+        .pop()
+        .unwrap_or_else(|| (*placed.interactable.clone(), InteractableScript::default()))
+}
+
+///What the dev tools have done, newest last, and what has been undone since
+#[derive(Resource, Debug, Default)]
+pub(super) struct EditHistory {
+    done: VecDeque<Edit>,
+    undone: Vec<Edit>,
+}
+
+impl EditHistory {
+    ///Remembers a new edit, anything that was undone can not be redone after this
+    pub fn push(&mut self, edit: Edit) {
+        self.remember_done(edit);
+        self.undone.clear();
+    }
+
+    fn remember_done(&mut self, edit: Edit) {
+        self.done.push_back(edit);
+        if self.done.len() > HISTORY_LENGTH {
+            self.done.pop_front();
+        }
+    }
+
+    ///Points every edit of an interactable at the entity it was spawned again as
+    fn respawned(&mut self, old: Entity, new: Entity) {
+        for edit in self.done.iter_mut().chain(self.undone.iter_mut()) {
+            if let Edit::Interactable { entity, .. } = edit {
+                if *entity == old {
+                    *entity = new;
+                }
+            }
+        }
+    }
+}
+
+///Everything an edit can touch when it is undone or redone
+#[derive(SystemParam)]
+struct EditTargets<'w, 's> {
+    commands: Commands<'w, 's>,
+    interactables: Query<'w, 's, (&'static mut Interactable, Option<&'static RoomId>)>,
+    collider_editor: Option<ResMut<'w, ColliderEditor>>,
+    collider_edits: EventWriter<'w, ColliderEdit>,
+    world_state: Res<'w, WorldState>,
+    asset_server: Res<'w, AssetServer>,
+    layouts: ResMut<'w, Assets<TextureAtlasLayout>>,
+}
+
+impl EditTargets<'_, '_> {
+    fn spawn(&mut self, placed: &Placed) -> Entity {
+        let (interactable, script) = with_script(placed);
+        let mut entity = self.commands.spawn((interactable, script));
+        if let Some(room) = &placed.room {
+            entity.insert(RoomId(room.clone()));
+        }
+        interactable_sprite::insert_sprite(
+            &mut entity,
+            &placed.interactable,
+            &self.world_state,
+            &self.asset_server,
+            &mut self.layouts,
+        );
+        entity.id()
+    }
+}
+
+//Ctrl+Z and Ctrl+Y are the usual editor shortcuts rather than game actions, so they are read straight from the keyboard
+fn undo_redo(
+    keys: Res<ButtonInput<KeyCode>>,
+    typing: Option<Res<TypingText>>,
+    mut history: ResMut<EditHistory>,
+    mut targets: EditTargets,
+) {
+    if typing.is_some() || !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    let undo = keys.just_pressed(KeyCode::KeyZ);
+    if !undo && !keys.just_pressed(KeyCode::KeyY) {
+        return;
+    }
+
+    //painting is only tracked by the open editor, reopening it reads the file again and would bring undone tiles back
+    let next = if undo { history.done.back() } else { history.undone.last() };
+    if let Some(edit @ Edit::Colliders { .. }) = next {
+        if targets.collider_editor.is_none() {
+            warn!("Open the collider editor to {} {}", if undo { "undo" } else { "redo" }, edit.describe());
+            return;
+        }
+    }
+
+    let edit = if undo { history.done.pop_back() } else { history.undone.pop() };
+    let Some(edit) = edit else {
+        info!("Nothing to {}", if undo { "undo" } else { "redo" });
+        return;
+    };
+
+    match edit.apply(&mut targets, undo) {
+        Ok(respawned) => {
+            info!("{} {}", if undo { "Undid" } else { "Redid" }, edit.describe());
+            let old = match &edit {
+                Edit::Interactable { entity, .. } => Some(*entity),
+                Edit::Colliders { .. } => None,
+            };
+
+            if undo {
+                history.undone.push(edit);
+            } else {
+                history.remember_done(edit);
+            }
+            if let (Some(old), Some(new)) = (old, respawned) {
+                history.respawned(old, new);
+            }
+        }
+        Err(message) => warn!("Could not {} {}, {}", if undo { "undo" } else { "redo" }, edit.describe(), message),
+    }
+}
+
+fn clear_history(mut history: ResMut<EditHistory>) {
+    *history = EditHistory::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moved(entity: Entity) -> Edit {
+        let interactable = Interactable::new("box".to_string(), Rect::new(0.0, 0.0, 1.0, 1.0), Vec::new());
+        Edit::Interactable {
+            entity,
+            before: Some(Placed::new(&interactable, None)),
+            after: Some(Placed::new(&interactable, None)),
+        }
+    }
+
+    #[test]
+    fn new_edits_drop_what_was_undone() {
+        let mut history = EditHistory::default();
+        history.push(moved(Entity::from_raw(1)));
+        let undone = history.done.pop_back().unwrap();
+        history.undone.push(undone);
+
+        history.push(moved(Entity::from_raw(2)));
+        assert_eq!(history.done.len(), 1);
+        assert!(history.undone.is_empty());
+    }
+
+    #[test]
+    fn respawned_interactables_keep_their_history() {
+        let (old, new) = (Entity::from_raw(1), Entity::from_raw(2));
+        let mut history = EditHistory::default();
+        history.push(moved(old));
+        history.undone.push(moved(old));
+
+        history.respawned(old, new);
+        for edit in history.done.iter().chain(&history.undone) {
+            assert!(matches!(edit, Edit::Interactable { entity, .. } if *entity == new));
+        }
+    }
+}
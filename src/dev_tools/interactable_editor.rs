@@ -0,0 +1,636 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::game::interaction::{self, Facing, Interactable};
+use crate::game::rooms::{Room, RoomId};
+use crate::game::script;
+use crate::input::{ActionState, GameAction, TypingText};
+use crate::resources::{DevMode, GameState};
+use crate::PIXEL_SCALE;
+
+use super::collider_editor::ColliderEditor;
+use super::{cursor_position, spawn_panel, BUTTON_COLOR, SELECTED_BUTTON_COLOR, TEXT_COLOR};
+use super::history::{Edit, EditHistory, Placed};
+
+const DIM_TEXT_COLOR: Color = Color::srgb(0.6, 0.6, 0.65);
+const ERROR_TEXT_COLOR: Color = Color::srgb(1.0, 0.45, 0.4);
+
+const OUTLINE_COLOR: Color = Color::srgba(0.3, 0.5, 1.0, 0.6);
+const SELECTED_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+
+///How close to a corner of the selected interactable a click has to be to resize it
+const HANDLE_REACH: f32 = PIXEL_SCALE / 4.0;
+
+const CORNERS: [Vec2; 4] = [
+    Vec2::new(-1.0, -1.0),
+    Vec2::new(1.0, -1.0),
+    Vec2::new(-1.0, 1.0),
+    Vec2::new(1.0, 1.0),
+];
+
+const FACINGS: [Facing; 4] = [Facing::Up, Facing::Down, Facing::Left, Facing::Right];
+
+pub fn interactable_editor_plugin(app: &mut App) {
+    app.add_systems(Update, (
+        toggle_interactable_editor,
+        (
+            press_inspector_buttons,
+            type_into_field,
+            drag_interactables,
+            refresh_inspector,
+            draw_interactables,
+        ).chain().run_if(resource_exists::<InteractableEditor>),
+    ).chain().run_if(in_state(GameState::Running).and_then(resource_exists_and_equals(DevMode(true)))))
+
+    .add_systems(OnExit(GameState::Running), close_interactable_editor);
+}
+
+///Exists while the interactable editor is open
+/// click an interactable to select it, drag it to move it, or drag one of its corners to resize it
+#[derive(Resource, Debug, Default)]
+pub(super) struct InteractableEditor {
+    selected: Option<Entity>,
+    drag: Option<Drag>,
+    field: Option<TextField>,
+
+    //the last edit that was refused, shown at the bottom of the panel
+    message: Option<String>,
+
+    //the panel is built again when this is set
+    refresh: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Drag {
+    entity: Entity,
+    handle: Handle,
+    grabbed_at: Vec2,
+
+    //how it was when it was grabbed
+    start: Placed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Handle {
+    Body,
+
+    //which corner, as the direction from the center to it
+    Corner(Vec2),
+}
+
+///The lists of an interactable that can be edited as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum List {
+    Actions,
+    Dependancies,
+}
+
+///An entry being typed, it replaces entry `index` or is added on the end when that is None
+#[derive(Debug, Clone)]
+struct TextField {
+    list: List,
+    index: Option<usize>,
+    text: String,
+}
+
+//Tag components for the panel
+#[derive(Component)]
+struct InspectorPanel;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+enum InspectorButton {
+    Facing(Facing),
+    Edit(List, usize),
+    Remove(List, usize),
+    Add(List),
+    Delete,
+}
+
+fn entries(interactable: &Interactable, list: List) -> &Vec<String> {
+    match list {
+        List::Actions => &interactable.action,
+        List::Dependancies => &interactable.dependancies,
+    }
+}
+
+///Makes sure an entry will work before it goes in, actions have to parse and so do conditions
+/// a plain id is trusted, the level checks those when it loads
+fn check_entry(list: List, text: &str) -> Result<(), String> {
+    match list {
+        List::Actions => script::parse_action(text).map(drop),
+        List::Dependancies if text.contains(char::is_whitespace) => script::parse_condition(text).map(drop),
+        List::Dependancies => Ok(()),
+    }
+}
+
+///The interactable with entry `index` of a list replaced by `text`, or `text` added when `index` is None
+/// no text (or only whitespace) removes the entry instead
+fn edit_entry(interactable: &Interactable, list: List, index: Option<usize>, text: Option<&str>) -> Result<Interactable, String> {
+    let mut edited = interactable.clone();
+    let entries = match list {
+        List::Actions => &mut edited.action,
+        List::Dependancies => &mut edited.dependancies,
+    };
+
+    match (index, text.map(str::trim).filter(|text| !text.is_empty())) {
+        (Some(index), None) if index < entries.len() => {
+            entries.remove(index);
+        }
+        (index, Some(text)) => {
+            check_entry(list, text)?;
+            match index {
+                Some(index) if index < entries.len() => entries[index] = text.to_string(),
+                _ => entries.push(text.to_string()),
+            }
+        }
+        _ => {}
+    }
+
+    Ok(edited)
+}
+
+///Swaps in the edited interactable, parsing its actions again when they changed so using it runs the new ones
+fn apply_edit(
+    commands: &mut Commands,
+    history: &mut EditHistory,
+    entity: Entity,
+    (interactable, room): (&mut Interactable, Option<&RoomId>),
+    edited: Interactable,
+) {
+    if edited == *interactable {
+        return;
+    }
+
+    if edited.action != interactable.action {
+        //the new entry was checked as it was typed in, but the others may have come from a file with a bad one
+        let parsed = interaction::with_scripts(format!("interactable {:?}", edited.id), vec![edited.clone()]);
+        if let Some((_, script)) = parsed.into_iter().next() {
+            commands.entity(entity).insert(script);
+        }
+    }
+    history.push(Edit::Interactable {
+        entity,
+        before: Some(Placed::new(interactable, room)),
+        after: Some(Placed::new(&edited, room)),
+    });
+    *interactable = edited;
+}
+
+fn corner_position(boundary: Rect, corner: Vec2) -> Vec2 {
+    boundary.center() + corner * boundary.half_size()
+}
+
+///What a click at `point` would grab of an interactable, corners win over the middle
+fn handle_at(boundary: Rect, point: Vec2) -> Option<Handle> {
+    CORNERS
+        .into_iter()
+        .find(|corner| corner_position(boundary, *corner).distance(point) <= HANDLE_REACH)
+        .map(Handle::Corner)
+        .or_else(|| boundary.contains(point).then_some(Handle::Body))
+}
+
+///Where a boundary ends up after its handle has been dragged by `offset`, in whole tiles
+/// resizing never takes it below one tile
+fn dragged(start: Rect, handle: Handle, offset: Vec2) -> Rect {
+    let offset = (offset / PIXEL_SCALE).round() * PIXEL_SCALE;
+
+    match handle {
+        Handle::Body => Rect::from_corners(start.min + offset, start.max + offset),
+        Handle::Corner(corner) => {
+            let mut rect = start;
+            if corner.x > 0.0 {
+                rect.max.x = (start.max.x + offset.x).max(start.min.x + PIXEL_SCALE);
+            } else {
+                rect.min.x = (start.min.x + offset.x).min(start.max.x - PIXEL_SCALE);
+            }
+            if corner.y > 0.0 {
+                rect.max.y = (start.max.y + offset.y).max(start.min.y + PIXEL_SCALE);
+            } else {
+                rect.min.y = (start.min.y + offset.y).min(start.max.y - PIXEL_SCALE);
+            }
+            rect
+        }
+    }
+}
+
+fn toggle_interactable_editor(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    editor: Option<Res<InteractableEditor>>,
+    collider_editor: Option<Res<ColliderEditor>>,
+    panels: Query<Entity, With<InspectorPanel>>,
+) {
+    if !actions.just_pressed(GameAction::DevInteractableEditor) {
+        return;
+    }
+
+    if editor.is_some() {
+        close_interactable_editor(commands, panels);
+        return;
+    }
+
+    if collider_editor.is_some() {
+        warn!("Close the collider editor before editing interactables");
+        return;
+    }
+
+    info!("Interactable editor on: click to select, drag to move, drag a corner to resize");
+    commands.insert_resource(InteractableEditor { refresh: true, ..default() });
+
+    let style = Style { left: Val::Px(10.0), width: Val::Px(340.0), row_gap: Val::Px(4.0), ..default() };
+    spawn_panel(&mut commands, style, InspectorPanel);
+}
+
+fn press_inspector_buttons(
+    mut commands: Commands,
+    mut editor: ResMut<InteractableEditor>,
+    buttons: Query<(&Interaction, &InspectorButton), Changed<Interaction>>,
+    mut interactables: Query<(&mut Interactable, Option<&RoomId>)>,
+    mut history: ResMut<EditHistory>,
+) {
+    let Some(button) = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| *button)
+    else {
+        return;
+    };
+    let Some(entity) = editor.selected else {
+        return;
+    };
+    let Ok((mut interactable, room)) = interactables.get_mut(entity) else {
+        return;
+    };
+
+    editor.refresh = true;
+    editor.message = None;
+    editor.field = None;
+    commands.remove_resource::<TypingText>();
+
+    match button {
+        InspectorButton::Facing(facing) => {
+            let mut edited = interactable.clone();
+            match edited.valid_directions.iter().position(|valid| *valid == facing) {
+                Some(index) => {
+                    edited.valid_directions.remove(index);
+                }
+                None => edited.valid_directions.push(facing),
+            }
+            apply_edit(&mut commands, &mut history, entity, (&mut interactable, room), edited);
+        }
+        InspectorButton::Edit(list, index) => {
+            let text = entries(&interactable, list).get(index).cloned().unwrap_or_default();
+            editor.field = Some(TextField { list, index: Some(index), text });
+            commands.insert_resource(TypingText);
+        }
+        InspectorButton::Add(list) => {
+            editor.field = Some(TextField { list, index: None, text: String::new() });
+            commands.insert_resource(TypingText);
+        }
+        InspectorButton::Remove(list, index) => match edit_entry(&interactable, list, Some(index), None) {
+            Ok(edited) => apply_edit(&mut commands, &mut history, entity, (&mut interactable, room), edited),
+            Err(message) => editor.message = Some(message),
+        },
+        InspectorButton::Delete => {
+            info!("Deleted interactable {:?}", interactable.id);
+            history.push(Edit::Interactable { entity, before: Some(Placed::new(&interactable, room)), after: None });
+            commands.entity(entity).despawn_recursive();
+            editor.selected = None;
+        }
+    }
+}
+
+fn type_into_field(
+    mut commands: Commands,
+    mut editor: ResMut<InteractableEditor>,
+    mut keys: EventReader<KeyboardInput>,
+    mut interactables: Query<(&mut Interactable, Option<&RoomId>)>,
+    mut history: ResMut<EditHistory>,
+) {
+    let Some(mut field) = editor.field.take() else {
+        keys.clear();
+        return;
+    };
+
+    //Some(true) once Enter is pressed, Some(false) for Escape
+    let mut finished = None;
+    let mut typed = false;
+    for key in keys.read().filter(|key| key.state == ButtonState::Pressed) {
+        match &key.logical_key {
+            Key::Enter => finished = Some(true),
+            Key::Escape => finished = Some(false),
+            Key::Backspace => {
+                field.text.pop();
+            }
+            Key::Space => field.text.push(' '),
+            Key::Character(text) => field.text.push_str(text),
+            _ => continue,
+        }
+        typed = true;
+    }
+    if typed {
+        editor.refresh = true;
+    }
+
+    let edit = match (finished, editor.selected) {
+        (None, _) => {
+            editor.field = Some(field);
+            return;
+        }
+        (Some(true), Some(entity)) => interactables.get_mut(entity).ok().map(|found| (entity, found)),
+        _ => None,
+    };
+
+    if let Some((entity, (mut interactable, room))) = edit {
+        match edit_entry(&interactable, field.list, field.index, Some(&field.text)) {
+            Ok(edited) => {
+                apply_edit(&mut commands, &mut history, entity, (&mut interactable, room), edited);
+                editor.message = None;
+            }
+            Err(message) => {
+                //keep typing so it can be fixed
+                editor.message = Some(message);
+                editor.field = Some(field);
+                return;
+            }
+        }
+    }
+    commands.remove_resource::<TypingText>();
+}
+
+fn drag_interactables(
+    mut commands: Commands,
+    mut editor: ResMut<InteractableEditor>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    (windows, cameras): (Query<&Window, With<PrimaryWindow>>, Query<(&Camera, &GlobalTransform)>),
+    panel: Query<&Interaction, Or<(With<InspectorPanel>, With<InspectorButton>)>>,
+    mut interactables: Query<(Entity, &mut Interactable, Option<&RoomId>)>,
+    (rooms, mut history): (Query<&Room>, ResMut<EditHistory>),
+) {
+    let Some(cursor) = cursor_position(&windows, &cameras) else {
+        return;
+    };
+
+    if let Some(drag) = &editor.drag {
+        let Ok((_, mut interactable, _)) = interactables.get_mut(drag.entity) else {
+            editor.drag = None;
+            return;
+        };
+
+        let boundary = dragged(drag.start.interactable.boundary, drag.handle, cursor - drag.grabbed_at);
+        if interactable.boundary != boundary {
+            interactable.boundary = boundary;
+            editor.refresh = true;
+        }
+
+        if !mouse.pressed(MouseButton::Left) {
+            let Some(drag) = editor.drag.take() else {
+                return;
+            };
+
+            //dragged into another room, so it gets saved with that one
+            let mut after = Placed { interactable: Box::new(interactable.clone()), room: drag.start.room.clone() };
+            if let Some(room) = rooms.iter().find(|room| room.contains(boundary.center())) {
+                if after.room.as_ref() != Some(&room.identifier) {
+                    after.room = Some(room.identifier.clone());
+                    commands.entity(drag.entity).insert(RoomId(room.identifier.clone()));
+                }
+            }
+
+            if after.interactable != drag.start.interactable || after.room != drag.start.room {
+                history.push(Edit::Interactable { entity: drag.entity, before: Some(drag.start), after: Some(after) });
+            }
+        }
+        return;
+    }
+
+    if !mouse.just_pressed(MouseButton::Left) || panel.iter().any(|interaction| *interaction != Interaction::None) {
+        return;
+    }
+
+    //the selected one's corners come first, they can poke out over its neighbours
+    let grabbed = editor
+        .selected
+        .and_then(|entity| interactables.get(entity).ok())
+        .and_then(|(entity, interactable, _)| Some((entity, handle_at(interactable.boundary, cursor)?)))
+        .or_else(|| {
+            //the smallest one under the cursor, so things inside bigger things can still be picked
+            interactables
+                .iter()
+                .filter(|(_, interactable, _)| interactable.boundary.contains(cursor))
+                .min_by(|(_, a, _), (_, b, _)| a.boundary.size().element_product().total_cmp(&b.boundary.size().element_product()))
+                .map(|(entity, _, _)| (entity, Handle::Body))
+        });
+
+    editor.field = None;
+    editor.message = None;
+    editor.refresh = true;
+    commands.remove_resource::<TypingText>();
+
+    match grabbed {
+        Some((entity, handle)) => {
+            editor.selected = Some(entity);
+            if let Ok((_, interactable, room)) = interactables.get(entity) {
+                let start = Placed::new(interactable, room);
+                editor.drag = Some(Drag { entity, handle, grabbed_at: cursor, start });
+            }
+        }
+        None => editor.selected = None,
+    }
+}
+
+fn refresh_inspector(
+    mut commands: Commands,
+    mut editor: ResMut<InteractableEditor>,
+    panels: Query<Entity, With<InspectorPanel>>,
+    interactables: Query<Ref<Interactable>>,
+) {
+    //the selection goes away when its room unloads
+    if editor.selected.is_some_and(|entity| interactables.get(entity).is_err()) {
+        editor.selected = None;
+        editor.drag = None;
+        editor.field = None;
+        editor.refresh = true;
+        commands.remove_resource::<TypingText>();
+    }
+
+    //or changes out from under the panel, like when an edit is undone
+    let changed = editor
+        .selected
+        .and_then(|entity| interactables.get(entity).ok())
+        .is_some_and(|interactable| interactable.is_changed());
+    if !editor.refresh && !changed {
+        return;
+    }
+    editor.refresh = false;
+
+    let selected = editor.selected.and_then(|entity| interactables.get(entity).ok());
+    let selected = selected.as_deref();
+    for panel in &panels {
+        commands
+            .entity(panel)
+            .despawn_descendants()
+            .with_children(|parent| build_inspector(parent, selected, &editor));
+    }
+}
+
+fn build_inspector(parent: &mut ChildBuilder, selected: Option<&Interactable>, editor: &InteractableEditor) {
+    let Some(interactable) = selected else {
+        label(parent, "Click an interactable to select it", DIM_TEXT_COLOR);
+        return;
+    };
+
+    label(parent, if interactable.id.is_empty() { "(no id)" } else { &interactable.id }, TEXT_COLOR);
+    let size = interactable.boundary.size() / PIXEL_SCALE;
+    let at = interactable.boundary.min / PIXEL_SCALE;
+    label(parent, format!("{} x {} tiles at {}, {}", size.x, size.y, at.x, at.y), DIM_TEXT_COLOR);
+
+    label(parent, "Facing", DIM_TEXT_COLOR);
+    row(parent, |row| {
+        for facing in FACINGS {
+            let valid = interactable.valid_directions.contains(&facing);
+            button(row, format!("{facing:?}"), InspectorButton::Facing(facing), valid);
+        }
+    });
+
+    for (list, title) in [(List::Actions, "Actions"), (List::Dependancies, "Dependancies")] {
+        label(parent, title, DIM_TEXT_COLOR);
+
+        let typing = editor.field.as_ref().filter(|field| field.list == list);
+        for (index, entry) in entries(interactable, list).iter().enumerate() {
+            let (text, editing) = match typing {
+                Some(field) if field.index == Some(index) => (format!("{}_", field.text), true),
+                _ => (entry.clone(), false),
+            };
+            row(parent, |row| {
+                button(row, text, InspectorButton::Edit(list, index), editing);
+                button(row, "x", InspectorButton::Remove(list, index), false);
+            });
+        }
+
+        match typing {
+            Some(field) if field.index.is_none() => button(parent, format!("{}_", field.text), InspectorButton::Add(list), true),
+            _ => button(parent, "+ add", InspectorButton::Add(list), false),
+        }
+    }
+
+    button(parent, "Delete", InspectorButton::Delete, false);
+
+    if editor.field.is_some() {
+        label(parent, "Enter keeps it, Escape throws it away", DIM_TEXT_COLOR);
+    }
+    if let Some(message) = &editor.message {
+        label(parent, message.as_str(), ERROR_TEXT_COLOR);
+    }
+}
+
+fn label(parent: &mut ChildBuilder, text: impl Into<String>, color: Color) {
+    parent.spawn(TextBundle::from_section(text, TextStyle { font_size: 18.0, color, ..default() }));
+}
+
+fn row(parent: &mut ChildBuilder, children: impl FnOnce(&mut ChildBuilder)) {
+    parent
+        .spawn(NodeBundle {
+            style: Style { column_gap: Val::Px(4.0), ..default() },
+            ..default()
+        })
+        .with_children(children);
+}
+
+fn button(parent: &mut ChildBuilder, text: impl Into<String>, button: InspectorButton, selected: bool) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(3.0)),
+                    flex_grow: if matches!(button, InspectorButton::Edit(..)) { 1.0 } else { 0.0 },
+                    ..default()
+                },
+                background_color: if selected { SELECTED_BUTTON_COLOR } else { BUTTON_COLOR }.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| label(parent, text, TEXT_COLOR));
+}
+
+fn draw_interactables(
+    editor: Res<InteractableEditor>,
+    interactables: Query<(Entity, &Interactable)>,
+    mut gizmos: Gizmos,
+) {
+    for (entity, interactable) in &interactables {
+        let boundary = interactable.boundary;
+        if editor.selected != Some(entity) {
+            gizmos.rect_2d(boundary.center(), 0.0, boundary.size(), OUTLINE_COLOR);
+            continue;
+        }
+
+        gizmos.rect_2d(boundary.center(), 0.0, boundary.size(), SELECTED_COLOR);
+        for corner in CORNERS {
+            gizmos.circle_2d(corner_position(boundary, corner), HANDLE_REACH, SELECTED_COLOR);
+        }
+    }
+}
+
+fn close_interactable_editor(mut commands: Commands, panels: Query<Entity, With<InspectorPanel>>) {
+    for entity in &panels {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<InteractableEditor>();
+    commands.remove_resource::<TypingText>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect::new(x0 * PIXEL_SCALE, y0 * PIXEL_SCALE, x1 * PIXEL_SCALE, y1 * PIXEL_SCALE)
+    }
+
+    #[test]
+    fn dragging_moves_and_resizes_in_whole_tiles() {
+        let start = tiles(2.0, 2.0, 4.0, 3.0);
+
+        assert_eq!(dragged(start, Handle::Body, Vec2::new(1.4, -0.6) * PIXEL_SCALE), tiles(3.0, 1.0, 5.0, 2.0));
+        assert_eq!(
+            dragged(start, Handle::Corner(Vec2::new(1.0, 1.0)), Vec2::new(1.0, 2.0) * PIXEL_SCALE),
+            tiles(2.0, 2.0, 5.0, 5.0)
+        );
+
+        //dragging a corner past the opposite one stops at a single tile
+        assert_eq!(
+            dragged(start, Handle::Corner(Vec2::new(-1.0, -1.0)), Vec2::new(5.0, 5.0) * PIXEL_SCALE),
+            tiles(3.0, 2.0, 4.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn corners_are_grabbed_before_the_middle() {
+        let boundary = tiles(0.0, 0.0, 2.0, 2.0);
+
+        assert_eq!(handle_at(boundary, Vec2::splat(2.0 * PIXEL_SCALE + 1.0)), Some(Handle::Corner(Vec2::new(1.0, 1.0))));
+        assert_eq!(handle_at(boundary, Vec2::splat(PIXEL_SCALE)), Some(Handle::Body));
+        assert_eq!(handle_at(boundary, Vec2::splat(3.0 * PIXEL_SCALE)), None);
+    }
+
+    #[test]
+    fn entries_are_checked_before_they_go_in() {
+        let interactable = Interactable::new("box".to_string(), tiles(0.0, 0.0, 1.0, 1.0), FACINGS.to_vec());
+
+        let edited = edit_entry(&interactable, List::Actions, None, Some(" say Hello ")).unwrap();
+        assert_eq!(edited.action, vec!["say Hello"]);
+        let edited = edit_entry(&edited, List::Actions, Some(0), Some("set_flag lights_on")).unwrap();
+        assert_eq!(edited.action, vec!["set_flag lights_on"]);
+        let edited = edit_entry(&edited, List::Actions, Some(0), None).unwrap();
+        assert!(edited.action.is_empty());
+
+        assert!(edit_entry(&interactable, List::Actions, None, Some("dance wildly")).is_err());
+        assert!(edit_entry(&interactable, List::Dependancies, None, Some("has the key")).is_err());
+        let edited = edit_entry(&interactable, List::Dependancies, None, Some("drawer")).unwrap();
+        assert_eq!(edited.dependancies, vec!["drawer"]);
+    }
+}
@@ -1,116 +1,173 @@
+use std::collections::HashSet;
+
+use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+mod collider_editor;
+mod history;
+mod interactable_editor;
 
-use crate::{IS_IN_WINDOWS, PIXEL_SCALE};
+use crate::input::{ActionState, GameAction};
+use history::{Edit, EditHistory, Placed};
+use crate::PIXEL_SCALE;
 
 use super::game::{
-    interaction::{Facing, Interactable},
+    interactables_file,
+    interaction::{Facing, Interactable, InteractableRegistry},
+    rooms::{Room, RoomId},
     Player, Shadow,
 };
-use super::resources::{CurrentLevel, DebugMode, DevMode};
+use super::resources::{DevMode, GameState};
 
 pub fn dev_tools(app: &mut App) {
+    app.add_plugins((
+        collider_editor::collider_editor_plugin,
+        interactable_editor::interactable_editor_plugin,
+        history::history_plugin,
+    ));
     app.add_systems(
         Update,
         (
             insert_interactable, save_interactables
-        ).run_if(resource_exists_and_equals(DevMode(true))),
+        ).run_if(in_state(GameState::Running).and_then(resource_exists_and_equals(DevMode(true)))),
     );
 }
 
 fn insert_interactable(
     mut commands: Commands,
-    players: Query<(Entity, &Player, &Transform), Without<Shadow>>,
-    input: Res<ButtonInput<KeyCode>>,
-    in_debug: Res<DebugMode>,
+    players: Query<&Transform, (With<Player>, Without<Shadow>)>,
+    actions: Res<ActionState>,
+    (registry, mut history): (Res<InteractableRegistry>, ResMut<EditHistory>),
+    rooms: Query<&Room>,
     in_dev: Res<DevMode>,
-    asset_server: Res<AssetServer>,
 ) {
     if !in_dev.0 {
         return;
     }
 
-    let _ = players;
-    //detect a keyboard press, when the "I" key is pressed, spawn an interactable object
-    if input.just_pressed(KeyCode::KeyI) {
-        let player = players.iter().next().unwrap();
-        let player_transform = player.2;
+    //when the place interactable action is pressed (I by default), spawn an interactable object
+    if actions.just_pressed(GameAction::DevPlaceInteractable) {
+        let Ok(player_transform) = players.get_single() else {
+            return;
+        };
 
         //snap all interactales to the nearest rounded grid space (PIXEL_SCALE)
         let x = (player_transform.translation.x / PIXEL_SCALE).round() * PIXEL_SCALE;
         let y = (player_transform.translation.y / PIXEL_SCALE).round() * PIXEL_SCALE;
 
+        //a unique placeholder id, rename it in the saved file to something meaningful
+        let mut index = registry.len();
+        while registry.get(&format!("interactable_{index}")).is_some() {
+            index += 1;
+        }
+
         let interactable = Interactable::new(
+            format!("interactable_{index}"),
             Rect::new(x, y, x + PIXEL_SCALE, y + PIXEL_SCALE),
             vec![Facing::Up, Facing::Down, Facing::Left, Facing::Right],
         );
 
-        commands.spawn(interactable);
-
-        if in_debug.0 {
-            let tex;
-
-            if IS_IN_WINDOWS {
-                tex = asset_server.load("textures\\rooms\\cldr.png");
-            } else {
-                tex = asset_server.load("textures/rooms/cldr.png");
+        //it belongs to whichever room it was placed in, and gets saved with that room
+        let room = rooms
+            .iter()
+            .find(|room| room.contains(Vec2::new(x, y)))
+            .map(|room| RoomId(room.identifier.clone()));
+        let placed = Placed::new(&interactable, room.as_ref());
+
+        let entity = match room {
+            Some(room) => commands.spawn((interactable, room)).id(),
+            None => {
+                warn!("Placed an interactable outside of every room, it will not be saved");
+                commands.spawn(interactable).id()
             }
-
-            commands.spawn(SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(1.0, 1.0)),
-                    ..Default::default()
-                },
-                transform: Transform {
-                    translation: Vec3::new(x, y, 50.0),
-                    scale: Vec3::new(PIXEL_SCALE, PIXEL_SCALE, 1.0),
-                    ..Default::default()
-                },
-                texture: tex,
-
-                ..Default::default()
-            });
-        }
+        };
+        history.push(Edit::Interactable { entity, before: None, after: Some(placed) });
     }
 }
 
+///Saves the spawned interactables of every loaded room into that room's interactables file
+/// the file is merged with rather than replaced, see `merge_interactables`, and a file that can not be read is left alone
 fn save_interactables(
-    interactables: Query<&Interactable>,
-    current_room: Res<CurrentLevel>,
+    interactables: Query<(&Interactable, &RoomId)>,
+    mut rooms: Query<&mut Room>,
 
-    input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
 ) {
-    if input.just_pressed(KeyCode::KeyP) {
-        let mut data: Vec<serde_json::Value> = Vec::new();
+    if !actions.just_pressed(GameAction::DevSaveInteractables) {
+        return;
+    }
 
-        for interactable in &interactables {
-            info!("Gathering Interactable for write: {:?}", interactable);
-            data.push(serde_json::to_value(interactable).unwrap());
+    for mut room in &mut rooms {
+        let spawned: Vec<Interactable> = interactables
+            .iter()
+            .filter(|(_, room_id)| room_id.0 == room.identifier)
+            .map(|(interactable, _)| interactable.clone())
+            .collect();
+
+        //a room with nothing out is either not loaded or never had anything, there is nothing to save either way
+        if spawned.is_empty() && (!room.is_active() || room.saved_interactables().next().is_none()) {
+            continue;
         }
 
-        let json_data = serde_json::to_string(&data).unwrap();
-        warn!("Saving Json payload:\n{}", json_data);
+        let path = room.interactables_path.clone();
+        let on_disk = if path.is_file() {
+            match interactables_file::read_interactables(&path) {
+                Ok(on_disk) => on_disk,
+                Err(err) => {
+                    error!("Not saving the interactables of room {}, {} could not be read: {}", room.identifier, path.display(), err);
+                    continue;
+                }
+            }
+        } else {
+            Vec::new()
+        };
 
-        let path = format!(
-            "assets/textures/rooms/L{}/interactables.json",
-            current_room.0
-        );
+        let loaded: HashSet<&str> = room.saved_interactables().map(|interactable| interactable.id.as_str()).collect();
+        let merged = interactables_file::merge_interactables(on_disk, &loaded, spawned);
 
-        if IS_IN_WINDOWS {
-            write_json(&json_data, &path.replace("/", "\\"))
-                .expect("Issue Creating or Writing file");
-        } else {
-            write_json(&json_data, &path).expect("Issue Creating or Writing file");
+        match interactables_file::write_interactables(&path, &merged) {
+            Ok(()) => {
+                info!("Saved {} interactables of room {} to {}", merged.len(), room.identifier, path.display());
+                room.set_saved_interactables(merged);
+            }
+            Err(err) => error!("Could not save the interactables of room {} to {}: {}", room.identifier, path.display(), err),
         }
-
-        info!("Interactables saved to: {}", path);
     }
 }
 
-fn write_json(json_string: &str, file_path: &str) -> Result<(), std::io::Error> {
-    info!("Attempting to create file: {}", file_path);
-    std::fs::write(file_path, json_string.as_bytes())?;
-    info!("File Created Successfully");
-    Ok(())
+const PANEL_COLOR: Color = Color::srgba(0.05, 0.05, 0.1, 0.9);
+const BUTTON_COLOR: Color = Color::srgb(0.2, 0.2, 0.25);
+const SELECTED_BUTTON_COLOR: Color = Color::srgb(0.45, 0.45, 0.55);
+const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+
+///Spawns an editor's panel along the top of the screen, `style` says which side it sits on and how it lays out its contents
+fn spawn_panel<'a>(commands: &'a mut Commands, style: Style, marker: impl Bundle) -> EntityCommands<'a> {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                ..style
+            },
+            background_color: PANEL_COLOR.into(),
+            z_index: ZIndex::Global(60),
+            ..default()
+        },
+        //so clicks on the panel can be told apart from clicks on the room
+        Interaction::default(),
+        marker,
+    ))
 }
 
-
+///Where the mouse is in the world, None when it is outside the window
+fn cursor_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use bevy::a11y::accesskit::Rect;
+use bevy::prelude::*;
+
+use crate::PIXEL_SCALE;
+
+///Size of one broadphase cell, a few tiles is plenty since most colliders are a tile or a wall
+const CELL_SIZE: f64 = PIXEL_SCALE as f64 * 4.0;
+
+///Uniform grid of collider entities so collision checks only look at colliders near the player
+/// spawn_colliders inserts into this and despawn_rooms removes from it, nothing else should need to touch it
+#[derive(Resource, Default, Debug)]
+pub struct ColliderGrid {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+
+    //which cells each entity was put in, so it can be removed without knowing its rect
+    entities: HashMap<Entity, Vec<(i32, i32)>>,
+}
+
+impl ColliderGrid {
+    pub fn insert(&mut self, entity: Entity, rect: Rect) {
+        //re-inserting moves the entity rather than duplicating it
+        self.remove(entity);
+
+        let cells: Vec<(i32, i32)> = cells_covering(rect).collect();
+        for cell in &cells {
+            self.cells.entry(*cell).or_default().push(entity);
+        }
+        self.entities.insert(entity, cells);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let Some(cells) = self.entities.remove(&entity) else {
+            return;
+        };
+
+        for cell in cells {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|e| *e != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entities.clear();
+    }
+
+    ///Every entity in a cell touched by `rect`, each listed once and sorted by entity
+    pub fn query(&self, rect: Rect) -> Vec<Entity> {
+        let mut found: Vec<Entity> = cells_covering(rect)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+}
+
+fn cells_covering(rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+    let min_x = (rect.x0 / CELL_SIZE).floor() as i32;
+    let max_x = (rect.x1 / CELL_SIZE).floor() as i32;
+    let min_y = (rect.y0 / CELL_SIZE).floor() as i32;
+    let max_y = (rect.y1 / CELL_SIZE).floor() as i32;
+
+    (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_only_returns_nearby_colliders() {
+        let mut grid = ColliderGrid::default();
+        let near = Entity::from_raw(1);
+        let far = Entity::from_raw(2);
+
+        grid.insert(near, Rect::new(0.0, 0.0, CELL_SIZE * 2.0, 10.0));
+        grid.insert(far, Rect::new(CELL_SIZE * 10.0, 0.0, CELL_SIZE * 11.0, 10.0));
+
+        assert_eq!(grid.query(Rect::new(CELL_SIZE * 1.5, 1.0, CELL_SIZE * 1.6, 2.0)), vec![near]);
+        assert_eq!(grid.query(Rect::new(0.0, 0.0, CELL_SIZE * 12.0, 1.0)), vec![near, far]);
+    }
+
+    #[test]
+    fn removed_colliders_are_forgotten() {
+        let mut grid = ColliderGrid::default();
+        let entity = Entity::from_raw(1);
+
+        grid.insert(entity, Rect::new(0.0, 0.0, 10.0, 10.0));
+        grid.insert(entity, Rect::new(0.0, 0.0, 10.0, 10.0));
+        assert_eq!(grid.len(), 1);
+
+        grid.remove(entity);
+        assert_eq!(grid.len(), 0);
+        assert!(grid.query(Rect::new(0.0, 0.0, 10.0, 10.0)).is_empty());
+        assert!(grid.cells.is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::svg::SvgRect;
+use super::ColliderType;
+
+///Greedily merges touching tile rects of the same ColliderType (and the same `data-*` attributes) into as few rectangles as it can
+/// Returns the merged rects and how many rects were removed
+///
+/// Rects that are not whole tiles are left exactly as they were
+pub fn merge_collider_rects(rects: &[(ColliderType, SvgRect)]) -> (Vec<(ColliderType, SvgRect)>, usize) {
+    let mut merged = Vec::new();
+
+    //collect the cells covered by each collider type, in the order the types first show up
+    //the first fill seen for a type is kept so merged rects still say what color they came from
+    //rects carrying different data (like exits to different rooms) are kept apart
+    let mut cells_by_type: Vec<(ColliderType, String, BTreeMap<String, String>, HashSet<(i32, i32)>)> = Vec::new();
+
+    for (style, rect) in rects {
+        if !is_whole_tiles(rect) {
+            merged.push((*style, rect.clone()));
+            continue;
+        }
+
+        let cells = match cells_by_type.iter_mut().find(|(s, _, data, _)| s == style && *data == rect.data) {
+            Some((_, _, _, cells)) => cells,
+            None => {
+                cells_by_type.push((*style, rect.fill.clone(), rect.data.clone(), HashSet::new()));
+                &mut cells_by_type.last_mut().unwrap().3
+            }
+        };
+
+        let (x, y) = (rect.x as i32, rect.y as i32);
+        for cy in y..y + rect.height as i32 {
+            for cx in x..x + rect.width as i32 {
+                cells.insert((cx, cy));
+            }
+        }
+    }
+
+    for (style, fill, data, cells) in cells_by_type {
+        //walk the cells top to bottom, left to right so the output does not depend on hash order
+        let mut ordered: Vec<(i32, i32)> = cells.iter().copied().collect();
+        ordered.sort_by_key(|&(x, y)| (y, x));
+
+        let mut used = HashSet::new();
+        let free = |cell: (i32, i32), used: &HashSet<(i32, i32)>| cells.contains(&cell) && !used.contains(&cell);
+
+        for (x, y) in ordered {
+            if used.contains(&(x, y)) {
+                continue;
+            }
+
+            //grow right as far as possible
+            let mut width = 1;
+            while free((x + width, y), &used) {
+                width += 1;
+            }
+
+            //then grow down while the whole row below is free
+            let mut height = 1;
+            while (x..x + width).all(|cx| free((cx, y + height), &used)) {
+                height += 1;
+            }
+
+            for cy in y..y + height {
+                for cx in x..x + width {
+                    used.insert((cx, cy));
+                }
+            }
+
+            merged.push((
+                style,
+                SvgRect {
+                    x: x as f32,
+                    y: y as f32,
+                    width: width as f32,
+                    height: height as f32,
+                    fill: fill.clone(),
+                    data: data.clone(),
+                },
+            ));
+        }
+    }
+
+    let removed = rects.len().saturating_sub(merged.len());
+    (merged, removed)
+}
+
+fn is_whole_tiles(rect: &SvgRect) -> bool {
+    [rect.x, rect.y, rect.width, rect.height]
+        .iter()
+        .all(|v| v.fract() == 0.0)
+        && rect.width > 0.0
+        && rect.height > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::game::broadphase::ColliderGrid;
+    use crate::game::svg::load_collider_svg;
+    use crate::game::{collision_detection, Collider, Player};
+    use crate::PIXEL_SCALE;
+
+    const BEDROOM: &str = "assets/textures/rooms/L1/_0_0/bedroom_cldr.svg";
+
+    fn typed_rects(path: &str) -> Vec<(ColliderType, SvgRect)> {
+        load_collider_svg(Path::new(path))
+            .unwrap()
+            .rects
+            .into_iter()
+            .map(|rect| {
+                let style = if rect.fill == "#000000" { ColliderType::RIGID } else { ColliderType::Interactable };
+                (style, rect)
+            })
+            .collect()
+    }
+
+    //same conversion as rooms::load_colliders for a room sitting at the origin
+    fn to_colliders(rects: &[(ColliderType, SvgRect)]) -> Vec<Collider> {
+        rects
+            .iter()
+            .map(|(style, rect)| Collider {
+                transform: Transform {
+                    translation: Vec3::new(rect.x * PIXEL_SCALE, -(rect.y * PIXEL_SCALE), 50.0),
+                    scale: Vec3::new(rect.width * PIXEL_SCALE, rect.height * PIXEL_SCALE, 0.0),
+                    ..default()
+                },
+                style: *style,
+                destination: None,
+            })
+            .collect()
+    }
+
+    fn resolve(colliders: &[Collider], start: Vec3) -> Vec3 {
+        let mut world = World::new();
+        let mut grid = ColliderGrid::default();
+        for collider in colliders {
+            let entity = world.spawn(collider.clone()).id();
+            grid.insert(entity, collider.rect());
+        }
+        world.insert_resource(grid);
+        let player = world
+            .spawn((
+                Transform::from_translation(start).with_scale(Vec3::new(PIXEL_SCALE, PIXEL_SCALE, 1.0)),
+                Player { vel_x: 0.0, vel_y: 0.0 },
+            ))
+            .id();
+        world.run_system_once(collision_detection);
+        world.get::<Transform>(player).unwrap().translation
+    }
+
+    //player hitbox as collision_detection builds it, for a player with a PIXEL_SCALE transform scale
+    fn overlapping(colliders: &[Collider], at: Vec3) -> usize {
+        let (left, right) = (at.x, at.x + PIXEL_SCALE * 0.625);
+        let (bot, top) = (at.y, at.y + PIXEL_SCALE * 0.2);
+
+        colliders
+            .iter()
+            .filter(|c| c.style == ColliderType::RIGID)
+            .filter(|c| {
+                let t = c.transform;
+                left < t.translation.x + t.scale.x
+                    && right > t.translation.x
+                    && bot < t.translation.y
+                    && top > t.translation.y - t.scale.y
+            })
+            .count()
+    }
+
+    fn sample_points() -> impl Iterator<Item = Vec3> {
+        (-20..60).flat_map(|iy| (-5..85).map(move |ix| Vec3::new(ix as f32 * 9.6, -(iy as f32) * 9.6, 0.0)))
+    }
+
+    #[test]
+    fn merges_bedroom_tiles() {
+        let rects = typed_rects(BEDROOM);
+        let (merged, removed) = merge_collider_rects(&rects);
+
+        assert!(removed > 0);
+        assert_eq!(merged.len() + removed, rects.len());
+    }
+
+    #[test]
+    fn covered_cells_are_unchanged() {
+        let cells = |rects: &[(ColliderType, SvgRect)]| {
+            let mut cells = HashSet::new();
+            for (style, rect) in rects {
+                for y in rect.y as i32..(rect.y + rect.height) as i32 {
+                    for x in rect.x as i32..(rect.x + rect.width) as i32 {
+                        cells.insert((format!("{style:?}"), x, y));
+                    }
+                }
+            }
+            cells
+        };
+
+        let rects = typed_rects(BEDROOM);
+        let (merged, _) = merge_collider_rects(&rects);
+
+        assert_eq!(cells(&rects), cells(&merged));
+
+        //merged rects must not overlap each other either
+        let total: f32 = merged.iter().map(|(_, r)| r.width * r.height).sum();
+        assert_eq!(total as usize, cells(&merged).len());
+    }
+
+    #[test]
+    fn player_hits_the_same_walls() {
+        let rects = typed_rects(BEDROOM);
+        let (merged, _) = merge_collider_rects(&rects);
+        let (before, after) = (to_colliders(&rects), to_colliders(&merged));
+
+        for at in sample_points() {
+            assert_eq!(overlapping(&before, at) > 0, overlapping(&after, at) > 0, "at {at:?}");
+        }
+    }
+
+    #[test]
+    fn collision_response_is_unchanged() {
+        let rects = typed_rects(BEDROOM);
+        let (merged, _) = merge_collider_rects(&rects);
+        let (before, after) = (to_colliders(&rects), to_colliders(&merged));
+
+        //straddling a seam between two tiles used to snag the player sideways, merging removes those seams
+        //so only compare places where the player touches at most one of the original tiles
+        for at in sample_points().filter(|at| overlapping(&before, *at) <= 1) {
+            assert_eq!(resolve(&before, at), resolve(&after, at), "at {at:?}");
+        }
+    }
+
+    #[test]
+    fn keeps_types_and_fractional_rects_apart() {
+        let rect = |x: f32, y: f32, width: f32, fill: &str| SvgRect {
+            x,
+            y,
+            width,
+            height: 1.0,
+            fill: fill.to_string(),
+            data: BTreeMap::new(),
+        };
+        let rects = vec![
+            (ColliderType::RIGID, rect(0.0, 0.0, 1.0, "#000000")),
+            (ColliderType::RIGID, rect(1.0, 0.0, 1.0, "#000000")),
+            (ColliderType::ChangeRoom, rect(2.0, 0.0, 1.0, "#00FF00")),
+            (ColliderType::RIGID, rect(3.0, 0.0, 0.5, "#000000")),
+        ];
+
+        let (merged, removed) = merge_collider_rects(&rects);
+
+        assert_eq!(removed, 1);
+        assert!(merged.contains(&(ColliderType::RIGID, rect(0.0, 0.0, 2.0, "#000000"))));
+        assert!(merged.contains(&(ColliderType::ChangeRoom, rect(2.0, 0.0, 1.0, "#00FF00"))));
+        assert!(merged.contains(&(ColliderType::RIGID, rect(3.0, 0.0, 0.5, "#000000"))));
+    }
+}
@@ -0,0 +1,611 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::resources::{GameState, PlayState};
+use crate::PIXEL_SCALE;
+
+use super::dialogue::{ActiveDialogue, DialogueQueue, DialogueSystems};
+use super::interaction::Facing;
+use super::movement::MovementConfig;
+use super::rooms::Room;
+use super::script::{parse_action, ActionCommand, ScriptContext};
+use super::{Player, Shadow};
+
+///Where cutscenes live, `cutscene <name>` plays `<name>.json` from here
+pub const CUTSCENE_DIR: &str = "assets/cutscenes";
+
+pub fn cutscene_plugin(app: &mut App) {
+    app
+        .add_event::<CutsceneRequest>()
+        .add_systems(Update, (
+            start_cutscenes,
+            run_cutscene.run_if(resource_exists::<ActiveCutscene>),
+        ).chain().before(DialogueSystems).run_if(in_state(GameState::Running)))
+
+        //a level change (a teleport in the middle of a cutscene) ends it
+        //rooms streaming in through GameState::Loading only pause it, and leaving Running resets the PlayState
+        .add_systems(OnEnter(GameState::LevelLoading), end_cutscene)
+        .add_systems(OnEnter(GameState::Running), resume_cutscene.run_if(resource_exists::<ActiveCutscene>));
+}
+
+///Asks for the cutscene in CUTSCENE_DIR with this name to play, after any that is already playing
+#[derive(Event, Debug, Clone)]
+pub struct CutsceneRequest(pub String);
+
+///A timeline of steps that play one after another, as written in CUTSCENE_DIR
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cutscene {
+    pub steps: Vec<CutsceneStep>,
+}
+
+impl Cutscene {
+    pub fn load(name: &str) -> Result<Self, Box<dyn Error>> {
+        let file_content = fs::read_to_string(format!("{CUTSCENE_DIR}/{name}.json"))?;
+        Ok(serde_json::from_str(&file_content)?)
+    }
+}
+
+///One step of a cutscene, tiles are in the collider file of the room the player is in (x right, y down)
+///
+/// `move_player 3 4`, `pan_camera 8 2 1.5`, `pan_camera player 1`, `wait 2`, `fade_out 0.5`, `fade_in 0.5`
+/// or any interactable action, `say` and `dialogue` wait for the player to finish reading
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum CutsceneStep {
+    Action(ActionCommand),
+    Wait(f32),
+    MovePlayer(Vec2),
+
+    //None pans back to the player, who the camera follows again once it gets there
+    PanCamera { tile: Option<Vec2>, seconds: f32 },
+
+    //how dark the screen ends up, 1 is black
+    Fade { to: f32, seconds: f32 },
+}
+
+impl TryFrom<String> for CutsceneStep {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        parse_step(&text)
+    }
+}
+
+pub fn parse_step(text: &str) -> Result<CutsceneStep, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let number = |word: &str| word.parse::<f32>().map_err(|_| format!("`{word}` is not a number"));
+
+    match words[..] {
+        ["wait", seconds] => Ok(CutsceneStep::Wait(number(seconds)?)),
+        ["move_player", x, y] => Ok(CutsceneStep::MovePlayer(Vec2::new(number(x)?, number(y)?))),
+        ["pan_camera", "player", seconds] => Ok(CutsceneStep::PanCamera { tile: None, seconds: number(seconds)? }),
+        ["pan_camera", x, y, seconds] => Ok(CutsceneStep::PanCamera {
+            tile: Some(Vec2::new(number(x)?, number(y)?)),
+            seconds: number(seconds)?,
+        }),
+        ["fade_out", seconds] => Ok(CutsceneStep::Fade { to: 1.0, seconds: number(seconds)? }),
+        ["fade_in", seconds] => Ok(CutsceneStep::Fade { to: 0.0, seconds: number(seconds)? }),
+        ["wait" | "move_player" | "pan_camera" | "fade_out" | "fade_in", ..] => Err(format!(
+            "`{text}` is not a step, try `wait 2`, `move_player 3 4`, `pan_camera 8 2 1.5`, `pan_camera player 1` or `fade_out 0.5`"
+        )),
+        _ => parse_action(text).map(CutsceneStep::Action),
+    }
+}
+
+///Exists while a cutscene is playing, the game is in PlayState::Cutscene for as long as it is around
+#[derive(Resource, Debug)]
+pub struct ActiveCutscene {
+    steps: VecDeque<CutsceneStep>,
+    current: Option<StepProgress>,
+
+    //the camera stays where a pan left it instead of following the player
+    camera_held: bool,
+    overlay: Option<Entity>,
+}
+
+#[derive(Debug)]
+struct StepProgress {
+    step: CutsceneStep,
+    elapsed: f32,
+
+    //filled in when the step starts: where the player walks to, or where the camera pans from and to
+    from: Vec2,
+    to: Option<Vec2>,
+    from_alpha: f32,
+}
+
+//Tag component for the full screen node cutscenes fade with
+#[derive(Component)]
+struct CutsceneFade;
+
+///Everything on screen a cutscene moves around
+#[derive(SystemParam)]
+struct CutsceneStage<'w, 's> {
+    players: Query<'w, 's, (&'static mut Transform, &'static mut Facing), (With<Player>, Without<Shadow>)>,
+    shadows: Query<'w, 's, &'static mut Transform, (With<Shadow>, Without<Camera>)>,
+    camera: Query<'w, 's, &'static mut Transform, (With<Camera>, Without<Player>)>,
+    overlays: Query<'w, 's, &'static mut BackgroundColor, With<CutsceneFade>>,
+    rooms: Query<'w, 's, &'static Room>,
+    movement: Res<'w, MovementConfig>,
+    time: Res<'w, Time>,
+}
+
+impl CutsceneStage<'_, '_> {
+    fn player_position(&self) -> Option<Vec2> {
+        self.players.get_single().ok().map(|(transform, _)| transform.translation.truncate())
+    }
+
+    fn camera_position(&self) -> Vec2 {
+        self.camera.get_single().map(|transform| transform.translation.truncate()).unwrap_or_default()
+    }
+
+    fn set_camera(&mut self, position: Vec2) {
+        if let Ok(mut transform) = self.camera.get_single_mut() {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+
+    ///Where a tile is in whichever room the player is standing in
+    fn tile(&self, tile: Vec2) -> Option<Vec2> {
+        let player = self.player_position()?;
+        let room = self.rooms.iter().find(|room| room.contains(player));
+        if room.is_none() {
+            warn!("The player is not in a room, cutscene tiles have nothing to be relative to");
+        }
+        room.map(|room| room.tile_position(tile.x, tile.y))
+    }
+
+    fn alpha(&self) -> f32 {
+        self.overlays.get_single().map(|color| color.0.alpha()).unwrap_or(0.0)
+    }
+}
+
+fn start_cutscenes(
+    mut commands: Commands,
+    mut requests: EventReader<CutsceneRequest>,
+    active: Option<ResMut<ActiveCutscene>>,
+    mut play_state: ResMut<NextState<PlayState>>,
+) {
+    let mut steps = VecDeque::new();
+    for CutsceneRequest(name) in requests.read() {
+        match Cutscene::load(name) {
+            Ok(cutscene) => {
+                info!("Playing cutscene {}", name);
+                steps.extend(cutscene.steps);
+            }
+            Err(err) => error!("Could not load cutscene {}: {}", name, err),
+        }
+    }
+
+    if steps.is_empty() {
+        return;
+    }
+
+    match active {
+        Some(mut active) => active.steps.extend(steps),
+        None => {
+            commands.insert_resource(ActiveCutscene {
+                steps,
+                current: None,
+                camera_held: false,
+                overlay: None,
+            });
+            play_state.set(PlayState::Cutscene);
+        }
+    }
+}
+
+///Plays steps until one of them needs more time, the cutscene ends once it runs out of steps
+fn run_cutscene(
+    mut commands: Commands,
+    mut cutscene: ResMut<ActiveCutscene>,
+    mut context: ScriptContext,
+    mut stage: CutsceneStage,
+    (dialogue, dialogue_queue): (Option<Res<ActiveDialogue>>, Res<DialogueQueue>),
+    mut play_state: ResMut<NextState<PlayState>>,
+) {
+    let reading = dialogue.is_some() || !dialogue_queue.is_empty();
+    let delta = stage.time.delta_seconds();
+
+    loop {
+        let progress = match cutscene.current.take() {
+            Some(progress) => progress,
+            None => {
+                let Some(step) = cutscene.steps.pop_front() else {
+                    break;
+                };
+                match start_step(step, &mut commands, &mut cutscene, &mut context, &stage) {
+                    Some(progress) => progress,
+                    None => continue,
+                }
+            }
+        };
+
+        match update_step(progress, delta, reading, &mut cutscene, &mut stage) {
+            Some(progress) => {
+                cutscene.current = Some(progress);
+                break;
+            }
+            None => continue,
+        }
+    }
+
+    if !cutscene.camera_held {
+        if let Some(player) = stage.player_position() {
+            stage.set_camera(player);
+        }
+    }
+
+    if cutscene.current.is_none() && cutscene.steps.is_empty() {
+        info!("Cutscene finished");
+        if let Some(overlay) = cutscene.overlay {
+            commands.entity(overlay).despawn_recursive();
+        }
+        commands.remove_resource::<ActiveCutscene>();
+        play_state.set(PlayState::Playing);
+    }
+}
+
+///Does whatever a step does straight away, None when there is nothing left to wait for
+fn start_step(
+    step: CutsceneStep,
+    commands: &mut Commands,
+    cutscene: &mut ActiveCutscene,
+    context: &mut ScriptContext,
+    stage: &CutsceneStage,
+) -> Option<StepProgress> {
+    let mut progress = StepProgress {
+        step: step.clone(),
+        elapsed: 0.0,
+        from: Vec2::ZERO,
+        to: None,
+        from_alpha: 0.0,
+    };
+
+    match step {
+        CutsceneStep::Action(command) => {
+            context.run(&command);
+            //dialogue only opens later this frame, so give it a frame before checking on it
+            if matches!(command, ActionCommand::Say(_) | ActionCommand::Dialogue(_) | ActionCommand::If { .. }) {
+                return Some(progress);
+            }
+            None
+        }
+        CutsceneStep::Wait(_) => Some(progress),
+        CutsceneStep::MovePlayer(tile) => {
+            progress.to = Some(stage.tile(tile)?);
+            Some(progress)
+        }
+        CutsceneStep::PanCamera { tile, .. } => {
+            progress.from = stage.camera_position();
+            progress.to = match tile {
+                Some(tile) => Some(stage.tile(tile)?),
+                None => None,
+            };
+            cutscene.camera_held = true;
+            Some(progress)
+        }
+        CutsceneStep::Fade { .. } => {
+            if cutscene.overlay.is_none() {
+                cutscene.overlay = Some(commands.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                        background_color: Color::srgba(0.0, 0.0, 0.0, 0.0).into(),
+                        z_index: ZIndex::Global(90),
+                        ..default()
+                    },
+                    CutsceneFade,
+                )).id());
+            }
+            progress.from_alpha = stage.alpha();
+            Some(progress)
+        }
+    }
+}
+
+///Moves a step along by `delta` seconds, None once it is done
+fn update_step(
+    mut progress: StepProgress,
+    delta: f32,
+    reading: bool,
+    cutscene: &mut ActiveCutscene,
+    stage: &mut CutsceneStage,
+) -> Option<StepProgress> {
+    let first_frame = progress.elapsed == 0.0;
+    progress.elapsed += delta;
+
+    match progress.step {
+        CutsceneStep::Action(_) => (first_frame || reading).then_some(progress),
+        CutsceneStep::Wait(seconds) => (progress.elapsed < seconds).then_some(progress),
+        CutsceneStep::MovePlayer(_) => {
+            let target = progress.to?;
+            let step = stage.movement.max_speed * delta;
+            let (mut transform, mut facing) = stage.players.get_single_mut().ok()?;
+
+            let offset = target - transform.translation.truncate();
+            if let Some(new_facing) = Facing::from_direction(offset) {
+                facing.set_if_neq(new_facing);
+            }
+            let moved = if offset.length() <= step { offset } else { offset.normalize() * step };
+            transform.translation += moved.extend(0.0);
+
+            let feet = transform.translation;
+            for mut shadow in &mut stage.shadows {
+                shadow.translation.x = feet.x - (PIXEL_SCALE * 0.125);
+                shadow.translation.y = feet.y;
+            }
+
+            (offset.length() > step).then_some(progress)
+        }
+        CutsceneStep::PanCamera { seconds, .. } => {
+            //the player might be walking, so panning back aims wherever they are now
+            let target = progress.to.or_else(|| stage.player_position()).unwrap_or(progress.from);
+            let t = smoothstep(progress.elapsed / seconds.max(f32::EPSILON));
+            stage.set_camera(progress.from.lerp(target, t));
+
+            if t < 1.0 {
+                return Some(progress);
+            }
+            cutscene.camera_held = progress.to.is_some();
+            None
+        }
+        CutsceneStep::Fade { to, seconds } => {
+            let t = (progress.elapsed / seconds.max(f32::EPSILON)).min(1.0);
+            let alpha = progress.from_alpha + (to - progress.from_alpha) * t;
+            for mut color in &mut stage.overlays {
+                *color = Color::srgba(0.0, 0.0, 0.0, alpha).into();
+            }
+            (t < 1.0).then_some(progress)
+        }
+    }
+}
+
+///Eases in and out of a pan, `t` runs from 0 to 1
+fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn resume_cutscene(mut play_state: ResMut<NextState<PlayState>>) {
+    play_state.set(PlayState::Cutscene);
+}
+
+fn end_cutscene(
+    mut commands: Commands,
+    cutscene: Option<Res<ActiveCutscene>>,
+    overlays: Query<Entity, With<CutsceneFade>>,
+) {
+    if cutscene.is_some() {
+        warn!("Changed level in the middle of a cutscene, the rest of it is skipped");
+        commands.remove_resource::<ActiveCutscene>();
+    }
+    for entity in &overlays {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use bevy::state::app::StatesPlugin;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::game::broadphase::ColliderGrid;
+    use crate::game::dialogue::dialogue_plugin;
+    use crate::game::interaction::InteractableMemory;
+    use crate::game::inventory::Inventory;
+    use crate::game::rooms::{self, room_plugin};
+    use crate::game::world_state::WorldState;
+    use crate::input::ActionState;
+    use crate::resources::CurrentLevel;
+
+    const BEDROOM: &str = "assets/textures/rooms/L1/_0_0";
+
+    //the game Running in the bedroom with the player standing on tile 2,4, every update moves time on by a tenth of a second
+    fn test_app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, StatesPlugin, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlasLayout>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+            .insert_state(GameState::Running)
+            .add_sub_state::<PlayState>()
+            .insert_resource(CurrentLevel(1))
+            .init_resource::<WorldState>()
+            .init_resource::<InteractableMemory>()
+            .init_resource::<ColliderGrid>()
+            .init_resource::<MovementConfig>()
+            .init_resource::<ActionState>()
+            .add_plugins((cutscene_plugin, dialogue_plugin));
+
+        let bedroom = rooms::create_room(Path::new(BEDROOM)).unwrap();
+        let feet = bedroom.tile_position(2.0, 4.0);
+        app.world_mut().spawn(bedroom);
+        app.world_mut().spawn((
+            Player { vel_x: 0.0, vel_y: 0.0 },
+            Transform::from_translation(feet.extend(0.0)).with_scale(Vec3::splat(PIXEL_SCALE)),
+            Facing::Down,
+            Inventory::default(),
+        ));
+        app.world_mut().spawn((Camera::default(), Transform::default()));
+        app
+    }
+
+    fn play(app: &mut App, steps: &[&str]) {
+        let steps = steps.iter().map(|step| parse_step(step).unwrap()).collect();
+        app.world_mut().insert_resource(ActiveCutscene { steps, current: None, camera_held: false, overlay: None });
+        app.world_mut().resource_mut::<NextState<PlayState>>().set(PlayState::Cutscene);
+    }
+
+    fn updates(app: &mut App, count: usize) {
+        for _ in 0..count {
+            app.update();
+        }
+    }
+
+    fn flag(app: &App, name: &str) -> bool {
+        app.world().resource::<WorldState>().is_set(name)
+    }
+
+    fn playing(app: &App) -> bool {
+        app.world().contains_resource::<ActiveCutscene>()
+    }
+
+    fn fade_alpha(app: &mut App) -> Option<f32> {
+        let mut overlays = app.world_mut().query_filtered::<&BackgroundColor, With<CutsceneFade>>();
+        overlays.get_single(app.world()).ok().map(|color| color.0.alpha())
+    }
+
+    fn position<T: Component>(app: &mut App) -> Vec2 {
+        let mut transforms = app.world_mut().query_filtered::<&Transform, With<T>>();
+        transforms.single(app.world()).translation.truncate()
+    }
+
+    fn bedroom_tile(app: &mut App, x: f32, y: f32) -> Vec2 {
+        let mut rooms = app.world_mut().query::<&Room>();
+        rooms.single(app.world()).tile_position(x, y)
+    }
+
+    #[test]
+    fn entering_a_room_plays_its_cutscene() {
+        let mut app = test_app();
+        app.add_plugins(room_plugin);
+
+        //the player is standing in the bedroom, which is not active yet, so the first update walks into it
+        //and the room loads before anything on_enter asks for runs
+        updates(&mut app, 1);
+        assert!(!flag(&app, "seen_intro"));
+
+        updates(&mut app, 4);
+
+        assert!(playing(&app));
+        assert!(flag(&app, "seen_intro"));
+        assert_eq!(app.world().resource::<State<PlayState>>().get(), &PlayState::Cutscene);
+    }
+
+    #[test]
+    fn rooms_streaming_in_do_not_end_cutscenes() {
+        let mut app = test_app();
+        app.add_plugins(room_plugin);
+        play(&mut app, &["wait 2", "set_flag waited"]);
+        updates(&mut app, 5);
+
+        //the way through GameState::Loading is over in a couple of frames, the cutscene picks up where it was
+        app.world_mut().resource_mut::<NextState<GameState>>().set(GameState::Loading);
+        updates(&mut app, 4);
+        assert!(playing(&app));
+        assert_eq!(app.world().resource::<State<GameState>>().get(), &GameState::Running);
+        assert_eq!(app.world().resource::<State<PlayState>>().get(), &PlayState::Cutscene);
+
+        updates(&mut app, 20);
+        assert!(flag(&app, "waited"));
+    }
+
+    #[test]
+    fn waits_hold_up_the_next_step() {
+        let mut app = test_app();
+        play(&mut app, &["wait 0.5", "set_flag waited"]);
+
+        updates(&mut app, 3);
+        assert!(!flag(&app, "waited"));
+
+        updates(&mut app, 4);
+        assert!(flag(&app, "waited"));
+        assert!(!playing(&app));
+        assert_eq!(app.world().resource::<State<PlayState>>().get(), &PlayState::Playing);
+    }
+
+    #[test]
+    fn say_waits_for_the_dialogue_to_close() {
+        let mut app = test_app();
+        play(&mut app, &["say \"Hello\"", "set_flag read"]);
+
+        updates(&mut app, 10);
+        assert!(app.world().contains_resource::<ActiveDialogue>());
+        assert!(!flag(&app, "read"));
+
+        //as if the player read the line and closed the box
+        app.world_mut().remove_resource::<ActiveDialogue>();
+        updates(&mut app, 2);
+        assert!(flag(&app, "read"));
+        assert!(!playing(&app));
+    }
+
+    #[test]
+    fn fades_darken_the_screen_over_time() {
+        let mut app = test_app();
+        play(&mut app, &["fade_out 1", "wait 0.5", "fade_in 0.5"]);
+
+        updates(&mut app, 5);
+        let halfway = fade_alpha(&mut app).unwrap();
+        assert!((0.3..0.7).contains(&halfway), "{halfway}");
+
+        updates(&mut app, 7);
+        assert_eq!(fade_alpha(&mut app), Some(1.0));
+
+        //the overlay goes away with the cutscene
+        updates(&mut app, 10);
+        assert!(!playing(&app));
+        assert_eq!(fade_alpha(&mut app), None);
+    }
+
+    #[test]
+    fn panning_back_to_the_player_lets_the_camera_follow_again() {
+        let mut app = test_app();
+        play(&mut app, &["pan_camera 12 3 0.3", "move_player 4 6", "pan_camera player 0.3", "wait 5"]);
+
+        //the camera stays on the tile while the player walks
+        updates(&mut app, 6);
+        let tile = bedroom_tile(&mut app, 12.0, 3.0);
+        assert!(app.world().resource::<ActiveCutscene>().camera_held);
+        assert_eq!(position::<Camera>(&mut app), tile);
+        assert_ne!(position::<Player>(&mut app), bedroom_tile(&mut app, 2.0, 4.0));
+
+        updates(&mut app, 20);
+        assert!(!app.world().resource::<ActiveCutscene>().camera_held);
+        assert_eq!(position::<Player>(&mut app), bedroom_tile(&mut app, 4.0, 6.0));
+        assert_eq!(position::<Camera>(&mut app), position::<Player>(&mut app));
+    }
+
+    #[test]
+    fn parses_every_step() {
+        assert_eq!(parse_step("wait 1.5"), Ok(CutsceneStep::Wait(1.5)));
+        assert_eq!(parse_step("move_player 3 4"), Ok(CutsceneStep::MovePlayer(Vec2::new(3.0, 4.0))));
+        assert_eq!(
+            parse_step("pan_camera 8 2 1.5"),
+            Ok(CutsceneStep::PanCamera { tile: Some(Vec2::new(8.0, 2.0)), seconds: 1.5 })
+        );
+        assert_eq!(parse_step("pan_camera player 1"), Ok(CutsceneStep::PanCamera { tile: None, seconds: 1.0 }));
+        assert_eq!(parse_step("fade_out 0.5"), Ok(CutsceneStep::Fade { to: 1.0, seconds: 0.5 }));
+        assert_eq!(parse_step("fade_in 0.5"), Ok(CutsceneStep::Fade { to: 0.0, seconds: 0.5 }));
+    }
+
+    #[test]
+    fn anything_else_is_an_action() {
+        assert_eq!(parse_step("set_flag seen_intro"), Ok(CutsceneStep::Action(ActionCommand::SetFlag("seen_intro".to_string()))));
+        assert!(parse_step("wait a_bit").is_err());
+        assert!(parse_step("pan_camera 1 2").is_err());
+        assert!(parse_step("dance").is_err());
+    }
+
+    #[test]
+    fn cutscene_files_load() {
+        let cutscene = Cutscene::load("intro").unwrap();
+        assert!(!cutscene.steps.is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+use bevy::a11y::accesskit::Rect as WorldRect;
+use bevy::prelude::*;
+
+use crate::input::{ActionState, GameAction};
+use crate::resources::{DebugMode, GameState};
+
+use super::interaction::Interactable;
+use super::rooms::Room;
+use super::{player_rect, Collider, ColliderType, Player, Shadow};
+
+const RIGID_COLOR: Color = Color::srgb(1.0, 0.2, 0.2);
+const CHANGE_ROOM_COLOR: Color = Color::srgb(0.2, 1.0, 0.2);
+const INTERACTABLE_COLLIDER_COLOR: Color = Color::srgb(0.3, 0.5, 1.0);
+const ACTIVE_ROOM_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
+const INACTIVE_ROOM_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.3);
+const PLAYER_COLOR: Color = Color::srgb(1.0, 0.9, 0.2);
+const INTERACTABLE_COLOR: Color = Color::srgb(0.9, 0.3, 1.0);
+
+pub fn debug_overlay_plugin(app: &mut App) {
+    app
+        .init_resource::<DebugOverlay>()
+        .add_systems(Update, (
+            toggle_overlays,
+            draw_overlays,
+        ).chain().run_if(in_state(GameState::Running).and_then(resource_exists_and_equals(DebugMode(true)))));
+}
+
+///Which outlines are drawn over the world in debug mode, the DevToggle actions (F1 to F4) turn them on and off
+#[derive(Resource, Debug)]
+struct DebugOverlay {
+    colliders: bool,
+    rooms: bool,
+    player: bool,
+    interactables: bool,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        DebugOverlay {
+            colliders: true,
+            rooms: true,
+            player: true,
+            interactables: true,
+        }
+    }
+}
+
+fn collider_color(style: ColliderType) -> Color {
+    match style {
+        ColliderType::RIGID => RIGID_COLOR,
+        ColliderType::ChangeRoom => CHANGE_ROOM_COLOR,
+        ColliderType::Interactable => INTERACTABLE_COLLIDER_COLOR,
+    }
+}
+
+fn outline(gizmos: &mut Gizmos, rect: WorldRect, color: Color) {
+    let center = Vec2::new((rect.x0 + rect.x1) as f32 / 2.0, (rect.y0 + rect.y1) as f32 / 2.0);
+    gizmos.rect_2d(center, 0.0, Vec2::new(rect.width() as f32, rect.height() as f32), color);
+}
+
+fn toggle_overlays(actions: Res<ActionState>, mut overlay: ResMut<DebugOverlay>) {
+    let overlay = &mut *overlay;
+    let toggles = [
+        (GameAction::DevToggleColliders, "collider", &mut overlay.colliders),
+        (GameAction::DevToggleRooms, "room", &mut overlay.rooms),
+        (GameAction::DevTogglePlayer, "player", &mut overlay.player),
+        (GameAction::DevToggleInteractables, "interactable", &mut overlay.interactables),
+    ];
+    for (action, name, shown) in toggles {
+        if actions.just_pressed(action) {
+            *shown = !*shown;
+            info!("Debug {} outlines {}", name, if *shown { "on" } else { "off" });
+        }
+    }
+}
+
+fn draw_overlays(
+    overlay: Res<DebugOverlay>,
+    colliders: Query<&Collider>,
+    rooms: Query<&Room>,
+    players: Query<&Transform, (With<Player>, Without<Shadow>)>,
+    interactables: Query<&Interactable>,
+    mut gizmos: Gizmos,
+) {
+    if overlay.rooms {
+        for room in &rooms {
+            let color = if room.is_active() { ACTIVE_ROOM_COLOR } else { INACTIVE_ROOM_COLOR };
+            outline(&mut gizmos, room.area(), color);
+        }
+    }
+
+    if overlay.colliders {
+        for collider in &colliders {
+            outline(&mut gizmos, collider.rect(), collider_color(collider.style));
+        }
+    }
+
+    if overlay.interactables {
+        for interactable in &interactables {
+            let boundary = interactable.boundary;
+            gizmos.rect_2d(boundary.center(), 0.0, boundary.size(), INTERACTABLE_COLOR);
+        }
+    }
+
+    //the same strip collision_detection and player_movement test against
+    if overlay.player {
+        for transform in &players {
+            outline(&mut gizmos, player_rect(transform), PLAYER_COLOR);
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fs;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::input::{ActionState, GameAction};
+use crate::resources::GameState;
+
+use super::interaction;
+use super::script::{ActionCommand, Condition, ScriptContext};
+
+///Where dialogue trees live, `dialogue <name>` loads `<name>.json` from here
+pub const DIALOGUE_DIR: &str = "assets/dialogue";
+
+///How fast the typewriter effect writes out a line
+const CHARS_PER_SECOND: f32 = 40.0;
+
+const BOX_COLOR: Color = Color::srgba(0.05, 0.05, 0.1, 0.9);
+const SPEAKER_COLOR: Color = Color::srgb(0.95, 0.8, 0.4);
+const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+const CHOICE_COLOR: Color = Color::srgb(0.6, 0.6, 0.6);
+const SELECTED_CHOICE_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
+
+pub fn dialogue_plugin(app: &mut App) {
+    app
+        .add_event::<DialogueRequest>()
+        .init_resource::<DialogueQueue>()
+
+        //after interacting, so the press that closes a dialogue can not also open it again
+        .add_systems(Update, (
+            queue_dialogue_requests,
+            open_next_dialogue.run_if(not(resource_exists::<ActiveDialogue>)),
+            advance_dialogue.run_if(resource_exists::<ActiveDialogue>),
+            update_dialogue_box.run_if(resource_exists::<ActiveDialogue>),
+        ).chain().in_set(DialogueSystems).after(interaction::interact_with_objects).run_if(in_state(GameState::Running)));
+}
+
+///The systems that open, advance and draw dialogue
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DialogueSystems;
+
+///A conversation loaded from DIALOGUE_DIR
+#[derive(Debug, Clone, Deserialize)]
+pub struct DialogueTree {
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+///One box of text
+#[derive(Debug, Clone, Deserialize)]
+pub struct DialogueNode {
+    #[serde(default)]
+    pub speaker: String,
+    pub text: String,
+
+    //run as soon as the node is shown
+    #[serde(default)]
+    pub actions: Vec<ActionCommand>,
+
+    //where to go once the player continues, the first branch whose condition holds wins over `next`
+    #[serde(default)]
+    pub branches: Vec<DialogueBranch>,
+    #[serde(default)]
+    pub next: Option<String>,
+
+    //when there are any (that pass their condition) the player has to pick one instead of continuing
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DialogueBranch {
+    pub condition: Condition,
+    pub next: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DialogueChoice {
+    pub text: String,
+
+    //hidden unless this holds
+    #[serde(default)]
+    pub condition: Option<Condition>,
+
+    //run when picked
+    #[serde(default)]
+    pub actions: Vec<ActionCommand>,
+
+    //None ends the conversation
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+impl DialogueTree {
+    ///A conversation of a single line, used by the `say` action
+    pub fn line(text: String) -> Self {
+        let node = DialogueNode {
+            speaker: String::new(),
+            text,
+            actions: Vec::new(),
+            branches: Vec::new(),
+            next: None,
+            choices: Vec::new(),
+        };
+
+        DialogueTree {
+            start: "line".to_string(),
+            nodes: HashMap::from([("line".to_string(), node)]),
+        }
+    }
+
+    pub fn load(name: &str) -> Result<Self, Box<dyn Error>> {
+        let path = format!("{DIALOGUE_DIR}/{name}.json");
+        let file_content = fs::read_to_string(&path)?;
+        let tree: DialogueTree = serde_json::from_str(&file_content)?;
+
+        tree.validate().map_err(|err| format!("{path}: {err}"))?;
+        Ok(tree)
+    }
+
+    ///Makes sure every node that can be reached actually exists
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.nodes.contains_key(&self.start) {
+            return Err(format!("start node `{}` does not exist", self.start));
+        }
+
+        let mut names: Vec<&String> = self.nodes.keys().collect();
+        names.sort();
+        for name in names {
+            let node = &self.nodes[name];
+            let targets = node
+                .next
+                .iter()
+                .chain(node.branches.iter().map(|branch| &branch.next))
+                .chain(node.choices.iter().filter_map(|choice| choice.next.as_ref()));
+
+            for target in targets {
+                if !self.nodes.contains_key(target) {
+                    return Err(format!("node `{name}` leads to `{target}`, which does not exist"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DialogueNode {
+    ///Where continuing from this node goes, None ends the conversation
+    pub fn continue_to(&self, check: impl Fn(&Condition) -> bool) -> Option<&String> {
+        self.branches
+            .iter()
+            .find(|branch| check(&branch.condition))
+            .map(|branch| &branch.next)
+            .or(self.next.as_ref())
+    }
+}
+
+///Asks for a conversation to be shown, they are shown one after another if more than one is asked for
+#[derive(Event, Debug, Clone)]
+pub enum DialogueRequest {
+    Line(String),
+    Tree(String),
+}
+
+///Conversations waiting for the current one to finish
+#[derive(Resource, Debug, Default)]
+pub struct DialogueQueue(VecDeque<DialogueTree>);
+
+impl DialogueQueue {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+///Exists while a conversation is on screen, player movement and interaction are paused for as long as it is around
+#[derive(Resource, Debug)]
+pub struct ActiveDialogue {
+    tree: DialogueTree,
+    node: String,
+
+    //how many characters the typewriter has written so far
+    revealed: f32,
+
+    //indices into the node's choices that passed their conditions, and which of them is highlighted
+    choices: Vec<usize>,
+    selected: usize,
+}
+
+impl ActiveDialogue {
+    fn current(&self) -> &DialogueNode {
+        &self.tree.nodes[&self.node]
+    }
+
+    fn fully_revealed(&self) -> bool {
+        self.revealed as usize >= self.current().text.chars().count()
+    }
+
+    fn enter(&mut self, node: String, context: &mut ScriptContext) {
+        self.node = node;
+        self.revealed = 0.0;
+        self.selected = 0;
+
+        let current = self.current().clone();
+        for action in &current.actions {
+            context.run(action);
+        }
+
+        self.choices = current
+            .choices
+            .iter()
+            .enumerate()
+            .filter(|(_, choice)| choice.condition.as_ref().is_none_or(|condition| context.check(condition)))
+            .map(|(index, _)| index)
+            .collect();
+    }
+}
+
+//Tag components for the parts of the dialogue box
+#[derive(Component)]
+struct DialogueBox;
+
+#[derive(Component)]
+struct DialogueSpeaker;
+
+#[derive(Component)]
+struct DialogueText;
+
+#[derive(Component)]
+struct DialogueChoices;
+
+fn queue_dialogue_requests(mut requests: EventReader<DialogueRequest>, mut queue: ResMut<DialogueQueue>) {
+    for request in requests.read() {
+        match request {
+            DialogueRequest::Line(text) => queue.0.push_back(DialogueTree::line(text.clone())),
+            DialogueRequest::Tree(name) => match DialogueTree::load(name) {
+                Ok(tree) => queue.0.push_back(tree),
+                Err(err) => error!("Could not load dialogue {}: {}", name, err),
+            },
+        }
+    }
+}
+
+fn open_next_dialogue(mut commands: Commands, mut queue: ResMut<DialogueQueue>, mut context: ScriptContext) {
+    let Some(tree) = queue.0.pop_front() else {
+        return;
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(0.0),
+                    bottom: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(28.0),
+                    padding: UiRect::all(Val::Px(16.0)),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                background_color: BOX_COLOR.into(),
+                z_index: ZIndex::Global(50),
+                ..default()
+            },
+            DialogueBox,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 26.0, color: SPEAKER_COLOR, ..default() }),
+                DialogueSpeaker,
+            ));
+            parent.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 30.0, color: TEXT_COLOR, ..default() }),
+                DialogueText,
+            ));
+            parent.spawn((TextBundle::default(), DialogueChoices));
+        });
+
+    let start = tree.start.clone();
+    let mut dialogue = ActiveDialogue {
+        tree,
+        node: String::new(),
+        revealed: 0.0,
+        choices: Vec::new(),
+        selected: 0,
+    };
+    dialogue.enter(start, &mut context);
+    commands.insert_resource(dialogue);
+}
+
+///Interact skips the typewriter, then continues or picks the highlighted choice, up and down move between choices
+fn advance_dialogue(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut dialogue: ResMut<ActiveDialogue>,
+    boxes: Query<Entity, With<DialogueBox>>,
+    mut context: ScriptContext,
+) {
+    //the press that opened the dialogue should not also skip its first line
+    if dialogue.is_added() {
+        return;
+    }
+
+    let choice_count = dialogue.choices.len();
+    if dialogue.fully_revealed() && choice_count > 0 {
+        if actions.just_pressed(GameAction::MoveUp) {
+            dialogue.selected = (dialogue.selected + choice_count - 1) % choice_count;
+        }
+        if actions.just_pressed(GameAction::MoveDown) {
+            dialogue.selected = (dialogue.selected + 1) % choice_count;
+        }
+    }
+
+    if !actions.just_pressed(GameAction::Interact) {
+        return;
+    }
+
+    if !dialogue.fully_revealed() {
+        dialogue.revealed = dialogue.current().text.chars().count() as f32;
+        return;
+    }
+
+    let next = if choice_count > 0 {
+        let choice = dialogue.current().choices[dialogue.choices[dialogue.selected]].clone();
+        for action in &choice.actions {
+            context.run(action);
+        }
+        choice.next
+    } else {
+        dialogue.current().continue_to(|condition| context.check(condition)).cloned()
+    };
+
+    match next {
+        Some(node) => dialogue.enter(node, &mut context),
+        None => {
+            for entity in &boxes {
+                commands.entity(entity).despawn_recursive();
+            }
+            commands.remove_resource::<ActiveDialogue>();
+        }
+    }
+}
+
+fn update_dialogue_box(
+    mut dialogue: ResMut<ActiveDialogue>,
+    mut speakers: Query<&mut Text, (With<DialogueSpeaker>, Without<DialogueText>, Without<DialogueChoices>)>,
+    mut bodies: Query<&mut Text, (With<DialogueText>, Without<DialogueChoices>)>,
+    mut choice_lists: Query<&mut Text, (With<DialogueChoices>, Without<DialogueText>)>,
+    time: Res<Time>,
+) {
+    if !dialogue.fully_revealed() {
+        dialogue.revealed += CHARS_PER_SECOND * time.delta_seconds();
+    }
+
+    let node = dialogue.current();
+    let shown: String = node.text.chars().take(dialogue.revealed as usize).collect();
+
+    for mut text in &mut speakers {
+        text.sections[0].value.clone_from(&node.speaker);
+    }
+    for mut text in &mut bodies {
+        text.sections[0].value = shown.clone();
+    }
+
+    //choices only appear once the line has finished writing out
+    let sections: Vec<TextSection> = if dialogue.fully_revealed() {
+        dialogue
+            .choices
+            .iter()
+            .enumerate()
+            .map(|(position, index)| {
+                let selected = position == dialogue.selected;
+                let marker = if selected { "> " } else { "  " };
+                TextSection::new(
+                    format!("{marker}{}\n", node.choices[*index].text),
+                    TextStyle {
+                        font_size: 26.0,
+                        color: if selected { SELECTED_CHOICE_COLOR } else { CHOICE_COLOR },
+                        ..default()
+                    },
+                )
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for mut text in &mut choice_lists {
+        text.sections.clone_from(&sections);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_dialogue_loads() {
+        let tree = DialogueTree::load("bedroom_mirror").unwrap();
+        assert!(tree.nodes.contains_key(&tree.start));
+    }
+
+    #[test]
+    fn validation_catches_missing_nodes() {
+        let mut tree = DialogueTree::line("hi".to_string());
+        assert!(tree.validate().is_ok());
+
+        tree.nodes.get_mut("line").unwrap().next = Some("nowhere".to_string());
+        assert!(tree.validate().is_err());
+    }
+
+    #[test]
+    fn first_passing_branch_wins() {
+        let node: DialogueNode = serde_json::from_str(
+            r#"{
+                "text": "...",
+                "branches": [
+                    { "condition": "flag knows_secret", "next": "secret" },
+                    { "condition": "not flag met", "next": "intro" }
+                ],
+                "next": "small_talk"
+            }"#,
+        )
+        .unwrap();
+
+        let not_met_yet = |condition: &Condition| *condition == Condition::NotFlag("met".to_string());
+        assert_eq!(node.continue_to(not_met_yet).map(String::as_str), Some("intro"));
+        assert_eq!(node.continue_to(|_| false).map(String::as_str), Some("small_talk"));
+    }
+
+    #[test]
+    fn bad_actions_fail_to_load() {
+        let result = serde_json::from_str::<DialogueNode>(r#"{ "text": "...", "actions": ["dance"] }"#);
+        assert!(result.unwrap_err().to_string().contains("unknown command `dance`"));
+    }
+}
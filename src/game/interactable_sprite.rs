@@ -0,0 +1,261 @@
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use serde::{Deserialize, Serialize};
+
+use crate::resources::GameState;
+use crate::PIXEL_SCALE;
+
+use super::interaction::Interactable;
+use super::world_state::{FlagValue, WorldState};
+
+///Anything y-sorted sits between the decoration (11 to 21) and foreground (22 to 32) layers of the rooms
+const Y_SORT_LAYER: f32 = 21.0;
+
+pub fn interactable_sprite_plugin(app: &mut App) {
+    app
+        .add_systems(Update, (
+            update_interactable_frames,
+            follow_boundaries,
+        ).run_if(in_state(GameState::Running)))
+
+        //after everything has moved for the frame
+        .add_systems(PostUpdate, y_sort.before(TransformSystem::TransformPropagate));
+}
+
+///How an interactable looks, as written in its room's interactables file
+/// `image` is a single picture, or a sprite sheet when `frame_size` is given
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InteractableSprite {
+    //asset path, relative to `assets/`
+    pub image: String,
+
+    //size on screen in tiles, defaults to the size of the interactable's boundary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<[f32; 2]>,
+
+    //size of one frame in pixels, the sheet is cut into `columns` x `rows` of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_size: Option<[u32; 2]>,
+    #[serde(default = "one")]
+    pub columns: u32,
+    #[serde(default = "one")]
+    pub rows: u32,
+
+    #[serde(default)]
+    pub frame_by: FrameBy,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl InteractableSprite {
+    ///Which frame of the sheet to show, past the last frame the last one stays up
+    pub fn frame(&self, interaction_count: u32, world_state: &WorldState) -> usize {
+        let last = (self.columns * self.rows).max(1) as usize - 1;
+        self.frame_by.state(interaction_count, world_state).min(last)
+    }
+}
+
+///What picks the frame of an interactable's sprite sheet, written as `count` or `flag <name>`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum FrameBy {
+    //frame 0 until it is used, then frame 1, 2...
+    #[default]
+    Count,
+
+    //frame 0 while the flag is unset, 1 once it is set, or the flag's number
+    Flag(String),
+}
+
+impl FrameBy {
+    fn state(&self, interaction_count: u32, world_state: &WorldState) -> usize {
+        match self {
+            FrameBy::Count => interaction_count as usize,
+            FrameBy::Flag(name) => match world_state.get(name) {
+                Some(FlagValue::Int(value)) => (*value).max(0) as usize,
+                Some(value) => value.is_truthy() as usize,
+                None => 0,
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for FrameBy {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words[..] {
+            ["count"] => Ok(FrameBy::Count),
+            ["flag", name] => Ok(FrameBy::Flag(name.to_string())),
+            _ => Err(format!("frame_by should be `count` or `flag <name>`, not {text:?}")),
+        }
+    }
+}
+
+impl From<FrameBy> for String {
+    fn from(frame_by: FrameBy) -> Self {
+        match frame_by {
+            FrameBy::Count => "count".to_string(),
+            FrameBy::Flag(name) => format!("flag {name}"),
+        }
+    }
+}
+
+///Kept in front of or behind other y-sorted things by how low on the screen its base (the transform's origin) is
+#[derive(Component, Debug, Clone, Copy)]
+pub struct YSort;
+
+///z inside the y-sort layer, lower on screen is closer to the camera
+fn y_sort_z(y: f32) -> f32 {
+    Y_SORT_LAYER + 1.0 / (1.0 + f32::exp(0.1 * y / PIXEL_SCALE))
+}
+
+fn y_sort(mut sorted: Query<&mut Transform, (With<YSort>, Changed<Transform>)>) {
+    for mut transform in &mut sorted {
+        let z = y_sort_z(transform.translation.y);
+        //only touch it when it moved layers, so this does not keep marking it as changed
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
+
+///Gives an interactable that has a sprite everything it needs to be drawn, standing on the bottom of its boundary
+pub fn insert_sprite(
+    entity: &mut EntityCommands,
+    interactable: &Interactable,
+    world_state: &WorldState,
+    asset_server: &AssetServer,
+    layouts: &mut Assets<TextureAtlasLayout>,
+) {
+    let Some(sprite) = &interactable.sprite else {
+        return;
+    };
+
+    let base = sprite_base(interactable);
+    entity.insert((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(sprite_size(interactable, sprite)),
+                anchor: Anchor::BottomCenter,
+                ..default()
+            },
+            texture: asset_server.load(sprite.image.clone()),
+            transform: Transform {
+                translation: base.extend(y_sort_z(base.y)),
+                scale: Vec3::new(PIXEL_SCALE, PIXEL_SCALE, 1.0),
+                ..default()
+            },
+            ..default()
+        },
+        YSort,
+    ));
+
+    if let Some([width, height]) = sprite.frame_size {
+        let layout = TextureAtlasLayout::from_grid(UVec2::new(width, height), sprite.columns, sprite.rows, None, None);
+        entity.insert(TextureAtlas {
+            layout: layouts.add(layout),
+            index: sprite.frame(interactable.interaction_count, world_state),
+        });
+    }
+}
+
+fn sprite_size(interactable: &Interactable, sprite: &InteractableSprite) -> Vec2 {
+    sprite
+        .size
+        .map(Vec2::from)
+        .unwrap_or(interactable.boundary.size() / PIXEL_SCALE)
+}
+
+///The middle of the bottom edge of the boundary, where the sprite stands
+fn sprite_base(interactable: &Interactable) -> Vec2 {
+    Vec2::new(interactable.boundary.center().x, interactable.boundary.min.y)
+}
+
+///Keeps sprites standing on their boundary when it is moved or resized, like in the dev tools
+fn follow_boundaries(mut interactables: Query<(&Interactable, &mut Transform, &mut Sprite), Changed<Interactable>>) {
+    for (interactable, mut transform, mut sprite) in &mut interactables {
+        let Some(look) = &interactable.sprite else {
+            continue;
+        };
+
+        let base = sprite_base(interactable);
+        if transform.translation.truncate() != base {
+            transform.translation.x = base.x;
+            transform.translation.y = base.y;
+        }
+        let size = Some(sprite_size(interactable, look));
+        if sprite.custom_size != size {
+            sprite.custom_size = size;
+        }
+    }
+}
+
+///Flips sprite sheets to the frame their interaction count or flag asks for
+fn update_interactable_frames(
+    world_state: Res<WorldState>,
+    mut interactables: Query<(Ref<Interactable>, &mut TextureAtlas)>,
+) {
+    for (interactable, mut atlas) in &mut interactables {
+        if !world_state.is_changed() && !interactable.is_changed() {
+            continue;
+        }
+        let Some(sprite) = &interactable.sprite else {
+            continue;
+        };
+
+        let frame = sprite.frame(interactable.interaction_count, &world_state);
+        if atlas.index != frame {
+            atlas.index = frame;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawer(frame_by: &str) -> InteractableSprite {
+        serde_json::from_value(serde_json::json!({
+            "image": "textures/interactables/drawer.png",
+            "frame_size": [16, 16],
+            "columns": 2,
+            "frame_by": frame_by,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn frames_follow_the_interaction_count() {
+        let sprite = drawer("count");
+        let world_state = WorldState::default();
+
+        assert_eq!(sprite.frame(0, &world_state), 0);
+        assert_eq!(sprite.frame(1, &world_state), 1);
+        //there is no third frame
+        assert_eq!(sprite.frame(5, &world_state), 1);
+    }
+
+    #[test]
+    fn frames_follow_a_flag() {
+        let sprite = drawer("flag drawer_open");
+        let mut world_state = WorldState::default();
+
+        assert_eq!(sprite.frame(3, &world_state), 0);
+        world_state.set("drawer_open", FlagValue::Bool(true));
+        assert_eq!(sprite.frame(0, &world_state), 1);
+
+        assert!(serde_json::from_value::<FrameBy>(serde_json::json!("flag")).is_err());
+    }
+
+    #[test]
+    fn lower_things_are_drawn_in_front() {
+        assert!(y_sort_z(0.0) > y_sort_z(PIXEL_SCALE));
+        assert!(y_sort_z(-PIXEL_SCALE * 100.0) < Y_SORT_LAYER + 1.0);
+        assert!(y_sort_z(PIXEL_SCALE * 100.0) > Y_SORT_LAYER);
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use super::interaction::Interactable;
+
+///The version interactables files are written as
+/// 1 was a bare list of interactables, 2 wraps the list with its version and gives every interactable an id
+pub const INTERACTABLES_VERSION: u64 = 2;
+
+///Reads a room's interactables file, bringing files written by older versions up to date on the way
+pub fn read_interactables(path: &Path) -> Result<Vec<Interactable>, Box<dyn Error>> {
+    let file_content = fs::read_to_string(path)?;
+    let file = migrate(serde_json::from_str(&file_content)?)?;
+    Ok(serde_json::from_value(file["interactables"].clone())?)
+}
+
+fn file_version(file: &Value) -> Result<u64, String> {
+    match file {
+        Value::Array(_) => Ok(1),
+        Value::Object(fields) => fields
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| "the file has no version number".to_string()),
+        _ => Err("the file is not a list of interactables".to_string()),
+    }
+}
+
+///Upgrades the json of an interactables file one version at a time until it is current
+fn migrate(mut file: Value) -> Result<Value, String> {
+    loop {
+        file = match file_version(&file)? {
+            INTERACTABLES_VERSION => return Ok(file),
+            1 => {
+                let mut interactables = file;
+                give_ids(&mut interactables);
+                json!({ "version": 2, "interactables": interactables })
+            }
+            newer if newer > INTERACTABLES_VERSION => {
+                return Err(format!("version {newer} is newer than this build can read (up to {INTERACTABLES_VERSION})"))
+            }
+            unknown => return Err(format!("there is no version {unknown}")),
+        };
+    }
+}
+
+///Saving merges by id, so the interactables from before ids existed get a placeholder one
+fn give_ids(interactables: &mut Value) {
+    let Some(interactables) = interactables.as_array_mut() else {
+        return;
+    };
+
+    let mut taken: HashSet<String> = interactables
+        .iter()
+        .filter_map(|interactable| interactable["id"].as_str().map(str::to_string))
+        .collect();
+
+    let mut index = 0;
+    for interactable in interactables.iter_mut().filter_map(Value::as_object_mut) {
+        if interactable.get("id").and_then(Value::as_str).is_some_and(|id| !id.is_empty()) {
+            continue;
+        }
+        while taken.contains(&format!("interactable_{index}")) {
+            index += 1;
+        }
+        let id = format!("interactable_{index}");
+        taken.insert(id.clone());
+        interactable.insert("id".to_string(), Value::String(id));
+    }
+}
+
+///What a room's file should hold after a save: the file as it is now with the spawned interactables put over it by id
+/// ones that were loaded but are not spawned any more were deleted or moved to another room and are dropped,
+/// anything else in the file is kept, like an interactable added by hand since the level loaded
+pub fn merge_interactables(on_disk: Vec<Interactable>, loaded: &HashSet<&str>, spawned: Vec<Interactable>) -> Vec<Interactable> {
+    let mut spawned: Vec<Option<Interactable>> = spawned.into_iter().map(Some).collect();
+    let mut merged = Vec::new();
+
+    for old in on_disk {
+        let replacement = spawned
+            .iter_mut()
+            .find(|new| new.as_ref().is_some_and(|new| !new.id.is_empty() && new.id == old.id))
+            .and_then(Option::take);
+
+        match replacement {
+            Some(new) => merged.push(new),
+            None if loaded.contains(old.id.as_str()) => {}
+            None => merged.push(old),
+        }
+    }
+
+    merged.extend(spawned.into_iter().flatten());
+    merged
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+///Where the copy of a file from before the last save is kept
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling_path(path, ".bak")
+}
+
+///Writes the interactables as the current version without ever leaving `path` half written,
+/// they go to a temporary file that is renamed over it once complete, and the file it replaces becomes `<path>.bak`
+pub fn write_interactables(path: &Path, interactables: &[Interactable]) -> Result<(), Box<dyn Error>> {
+    let file_content = serde_json::to_string_pretty(&json!({
+        "version": INTERACTABLES_VERSION,
+        "interactables": interactables,
+    }))?;
+
+    let temporary_path = sibling_path(path, ".tmp");
+    let mut temporary = File::create(&temporary_path)?;
+    temporary.write_all(file_content.as_bytes())?;
+    temporary.sync_all()?;
+    drop(temporary);
+
+    if path.is_file() {
+        fs::copy(path, backup_path(path))?;
+    }
+    fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Rect;
+
+    use super::*;
+
+    fn named(id: &str, x: f32) -> Interactable {
+        Interactable::new(id.to_string(), Rect::new(x, 0.0, x + 1.0, 1.0), Vec::new())
+    }
+
+    fn ids(interactables: &[Interactable]) -> Vec<&str> {
+        interactables.iter().map(|interactable| interactable.id.as_str()).collect()
+    }
+
+    #[test]
+    fn old_files_are_migrated() {
+        let old = json!([
+            { "id": "interactable_0", "boundary": { "min": [0.0, 0.0], "max": [1.0, 1.0] }, "valid_directions": [], "interaction_count": 0, "action": [], "dependancies": [] },
+            { "boundary": { "min": [0.0, 0.0], "max": [1.0, 1.0] }, "valid_directions": [], "interaction_count": 0, "action": [], "dependancies": [] },
+        ]);
+
+        let file = migrate(old).unwrap();
+        assert_eq!(file["version"], INTERACTABLES_VERSION);
+        let interactables: Vec<Interactable> = serde_json::from_value(file["interactables"].clone()).unwrap();
+        assert_eq!(ids(&interactables), vec!["interactable_0", "interactable_1"]);
+
+        assert!(migrate(json!({ "version": INTERACTABLES_VERSION + 1, "interactables": [] })).is_err());
+    }
+
+    #[test]
+    fn saving_merges_by_id() {
+        let on_disk = vec![named("drawer", 0.0), named("deleted", 0.0), named("added_by_hand", 0.0)];
+        let loaded = HashSet::from(["drawer", "deleted"]);
+        let spawned = vec![named("new", 0.0), named("drawer", 5.0)];
+
+        let merged = merge_interactables(on_disk, &loaded, spawned);
+        assert_eq!(ids(&merged), vec!["drawer", "added_by_hand", "new"]);
+        assert_eq!(merged[0].boundary.min.x, 5.0);
+    }
+
+    #[test]
+    fn writes_keep_a_backup() {
+        let directory = std::env::temp_dir().join(format!("harken_interactables_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("interactables.json");
+        fs::write(&path, "[]").unwrap();
+
+        write_interactables(&path, &[named("drawer", 0.0)]).unwrap();
+        assert_eq!(ids(&read_interactables(&path).unwrap()), vec!["drawer"]);
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "[]");
+        assert!(!sibling_path(&path, ".tmp").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,27 +1,164 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
 use bevy::prelude::*;
 use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize};
+
+use crate::input::{ActionState, Binding, GameAction, InputBindings};
+use crate::resources::{CurrentLevel, GameState, PlayState};
+use crate::PIXEL_SCALE;
+
+use super::dialogue::ActiveDialogue;
+use super::interactable_sprite::InteractableSprite;
+use super::interactables_file;
+use super::inventory::{Inventory, InventoryOpen};
+use super::script::{self, Condition, InteractableScript};
+use super::world_state::WorldState;
+use super::transition::RoomTransition;
+use super::{player_rect, Player, Shadow};
+
+///How far in front of the player's feet they can reach to interact with something
+const REACH: f32 = PIXEL_SCALE * 0.5;
+
+const PROMPT_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
+const LOCKED_PROMPT_COLOR: Color = Color::srgb(0.7, 0.45, 0.45);
+
+///Name of the file in each room folder that lists the room's interactables
+pub const INTERACTABLES_FILE: &str = "interactables.json";
+
+pub fn interaction_plugin(app: &mut App) {
+    app
+        .add_event::<InteractionEvent>()
+        .init_resource::<InteractableRegistry>()
+        .init_resource::<InteractableMemory>()
+        .add_systems(PreUpdate, update_interactable_registry)
+        .add_systems(Startup, spawn_interaction_prompt)
+        .add_systems(Update, update_interaction_prompt.run_if(in_state(PlayState::Playing)))
+        .add_systems(OnExit(PlayState::Playing), hide_interaction_prompt)
+        .add_systems(Update, (
+            interact_with_objects
+                .run_if(in_state(PlayState::Playing))
+                .run_if(not(resource_exists::<RoomTransition>))
+                .run_if(not(resource_exists::<ActiveDialogue>))
+                .run_if(not(resource_exists::<InventoryOpen>)),
+            script::run_interaction_scripts,
+            remember_interactables,
+            log_interactions,
+        ).chain().run_if(in_state(GameState::Running)));
+}
+
+///Sent whenever the player successfully interacts with an Interactable
+#[derive(Event, Debug, Clone)]
+pub struct InteractionEvent {
+    pub entity: Entity,
+
+    //how many times it has been interacted with, including this one
+    pub interaction_count: u32,
+
+    //which way the player was facing when they did it
+    pub facing: Facing,
+}
+
+///Finds interactables by the id their author gave them, kept up to date as they spawn and despawn
+#[derive(Resource, Debug, Default)]
+pub struct InteractableRegistry(HashMap<String, Entity>);
+
+impl InteractableRegistry {
+    pub fn get(&self, id: &str) -> Option<Entity> {
+        self.0.get(id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+///What has happened to the interactables of every level, by level and then id
+/// rooms despawn their interactables when the player leaves, this is how they come back the way they were left
+#[derive(Resource, Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InteractableMemory(BTreeMap<u32, LevelMemory>);
+
+impl InteractableMemory {
+    pub fn level(&self, level: u32) -> Option<&LevelMemory> {
+        self.0.get(&level)
+    }
+
+    pub fn remember(&mut self, level: u32, interactable: &Interactable) {
+        if interactable.id.is_empty() {
+            return;
+        }
+        self.0.entry(level).or_default().0.insert(
+            interactable.id.clone(),
+            RememberedInteractable {
+                interaction_count: interactable.interaction_count,
+                dependancies: interactable.dependancies.clone(),
+            },
+        );
+    }
+
+    ///Puts back whatever was remembered about `interactable`, anything never used stays as the file describes it
+    pub fn restore(&self, level: u32, interactable: &mut Interactable) {
+        let Some(remembered) = self.level(level).and_then(|memory| memory.0.get(&interactable.id)) else {
+            return;
+        };
+        interactable.interaction_count = remembered.interaction_count;
+        interactable.dependancies = remembered.dependancies.clone();
+    }
+}
+
+///The remembered interactables of one level
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelMemory(BTreeMap<String, RememberedInteractable>);
+
+impl LevelMemory {
+    pub fn interaction_count(&self, id: &str) -> u32 {
+        self.0.get(id).map_or(0, |remembered| remembered.interaction_count)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RememberedInteractable {
+    pub interaction_count: u32,
+    pub dependancies: Vec<String>,
+}
 
 //interactable object component
-#[derive(Component, Debug, Reflect, serde::Deserialize)]
+#[derive(Component, Clone, Debug, PartialEq, Reflect, serde::Deserialize)]
 pub struct Interactable {
+    //author given name, unique within the level, other interactables list it in their dependancies
+    #[serde(default)]
+    pub id: String,
+
     pub boundary: Rect,
 
     pub valid_directions: Vec<Facing>,
     pub interaction_count: u32,
 
     pub action: Vec<String>,
+
+    //ids of interactables that have to be used first, or conditions like `flag lights_on` and `has bedroom_key`
     pub dependancies: Vec<String>,
+
+    //what it looks like, nothing is drawn without one
+    #[serde(default)]
+    #[reflect(ignore)]
+    pub sprite: Option<InteractableSprite>,
 }
 
 
 impl Interactable {
-    pub fn new(boundary: Rect, valid_directions: Vec<Facing>) -> Self {
+    pub fn new(id: String, boundary: Rect, valid_directions: Vec<Facing>) -> Self {
         Interactable {
+            id,
             action: Vec::new(),
             boundary,
             dependancies: Vec::new(),
             interaction_count: 0,
             valid_directions,
+            sprite: None,
         }
     }
 
@@ -29,16 +166,8 @@ impl Interactable {
         self.interaction_count += 1;
     }
     
-    pub fn add_dependancy(&mut self, entity: Entity) {
-        self.dependancies.push(entity.to_string());
-    }
-
-    pub fn clear_dependancy(&mut self, entity: Entity) {
-        self.dependancies.retain(|x| x.eq(&entity.to_string()));
-    }
-
-    pub fn has_dependancies(&self) -> bool {
-        !self.dependancies.is_empty()
+    pub fn can_interact_from(&self, facing: Facing) -> bool {
+        self.valid_directions.contains(&facing)
     }
 
 }
@@ -48,23 +177,42 @@ impl serde::Serialize for Interactable {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("Interactable", 5)?;
+        let mut state = serializer.serialize_struct("Interactable", 7)?;
+        state.serialize_field("id", &self.id)?;
         state.serialize_field("boundary", &self.boundary)?;
         state.serialize_field("valid_directions", &self.valid_directions)?;
         state.serialize_field("interaction_count", &self.interaction_count)?;
         state.serialize_field("action", &self.action)?;
         state.serialize_field("dependancies", &self.dependancies)?;
+        match &self.sprite {
+            Some(sprite) => state.serialize_field("sprite", sprite)?,
+            None => state.skip_field("sprite")?,
+        }
         state.end()
     }
 }
 
-#[derive(Component, Debug, Reflect, serde::Serialize, serde::Deserialize)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect, serde::Serialize, serde::Deserialize)]
 pub enum Facing {
     Up,
     Down,
     Left,
     Right,
 }
+impl Facing {
+    ///The facing for a movement direction, whichever axis is pushed furthest wins (sideways on an exact diagonal)
+    /// None when there is no movement at all
+    pub fn from_direction(direction: Vec2) -> Option<Facing> {
+        if direction == Vec2::ZERO {
+            None
+        } else if direction.x.abs() >= direction.y.abs() {
+            Some(if direction.x > 0.0 { Facing::Right } else { Facing::Left })
+        } else {
+            Some(if direction.y > 0.0 { Facing::Up } else { Facing::Down })
+        }
+    }
+}
+
 // impl serde::Serialize for Facing {
 //     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
 //     where
@@ -77,4 +225,530 @@ pub enum Facing {
 //             Facing::Right => serializer.serialize_str("Right"),
 //         }
 //     }
-// }   
\ No newline at end of file
+// }
+
+///Something wrong with how a set of interactables depend on each other
+#[derive(Debug, PartialEq)]
+pub enum DependancyError {
+    DuplicateId(String),
+    MissingId { interactable: String, missing: String },
+    BadCondition { interactable: String, condition: String, message: String },
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for DependancyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependancyError::DuplicateId(id) => write!(f, "more than one interactable has the id `{id}`"),
+            DependancyError::MissingId { interactable, missing } => {
+                write!(f, "`{interactable}` depends on `{missing}`, which does not exist")
+            }
+            DependancyError::BadCondition { interactable, condition, message } => {
+                write!(f, "`{interactable}` depends on `{condition}`: {message}")
+            }
+            DependancyError::Cycle(ids) => write!(f, "dependancy cycle: {}", ids.join(" -> ")),
+        }
+    }
+}
+
+///Checks that ids are unique, that every dependancy names a real interactable, and that nothing depends on itself
+/// directly or through others (it could never be used)
+pub fn validate_dependancies(interactables: &[Interactable]) -> Vec<DependancyError> {
+    let mut errors = Vec::new();
+
+    let mut graph: HashMap<&str, &[String]> = HashMap::new();
+    for interactable in interactables.iter().filter(|i| !i.id.is_empty()) {
+        if graph.insert(&interactable.id, &interactable.dependancies).is_some() {
+            errors.push(DependancyError::DuplicateId(interactable.id.clone()));
+        }
+    }
+
+    for (index, interactable) in interactables.iter().enumerate() {
+        let name = || if interactable.id.is_empty() { format!("#{index}") } else { interactable.id.clone() };
+
+        for dependancy in &interactable.dependancies {
+            match dependancy_condition(dependancy) {
+                Some(Err(message)) => errors.push(DependancyError::BadCondition {
+                    interactable: name(),
+                    condition: dependancy.clone(),
+                    message,
+                }),
+                Some(Ok(_)) => {}
+                None if !graph.contains_key(dependancy.as_str()) => {
+                    errors.push(DependancyError::MissingId { interactable: name(), missing: dependancy.clone() });
+                }
+                None => {}
+            }
+        }
+    }
+
+    //depth first search, anything reached again while still on the path closes a cycle
+    fn visit<'a>(
+        id: &'a str,
+        graph: &HashMap<&'a str, &'a [String]>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        errors: &mut Vec<DependancyError>,
+    ) {
+        if let Some(start) = path.iter().position(|on_path| *on_path == id) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|id| id.to_string()).collect();
+            cycle.push(id.to_string());
+            errors.push(DependancyError::Cycle(cycle));
+            return;
+        }
+        if !done.insert(id) {
+            return;
+        }
+
+        path.push(id);
+        for dependancy in graph.get(id).copied().unwrap_or_default() {
+            if let Some((key, _)) = graph.get_key_value(dependancy.as_str()) {
+                visit(key, graph, path, done, errors);
+            }
+        }
+        path.pop();
+    }
+
+    let mut ids: Vec<&str> = graph.keys().copied().collect();
+    ids.sort();
+    let mut done = HashSet::new();
+    for id in ids {
+        visit(id, &graph, &mut Vec::new(), &mut done, &mut errors);
+    }
+
+    errors
+}
+
+///Reads the interactables saved in `path`, each with its actions parsed into an InteractableScript
+pub fn load_interactables(path: &Path) -> Result<Vec<(Interactable, InteractableScript)>, Box<dyn Error>> {
+    Ok(with_scripts(path.display(), interactables_file::read_interactables(path)?))
+}
+
+///Parses the actions of interactables that came from `source` (a file, or the dev tools)
+/// an interactable with a bad action is still kept, it just does nothing when used
+pub fn with_scripts(source: impl fmt::Display, interactables: Vec<Interactable>) -> Vec<(Interactable, InteractableScript)> {
+    interactables
+        .into_iter()
+        .enumerate()
+        .map(|(index, interactable)| {
+            let script = match script::parse_actions(index, &interactable.action) {
+                Ok(script) => script,
+                Err(err) => {
+                    error!("Bad action in {}, {}", source, err);
+                    InteractableScript::default()
+                }
+            };
+            (interactable, script)
+        })
+        .collect()
+}
+
+///The area just in front of the player's feet that they can interact with
+pub fn interaction_probe(player_transform: &Transform, facing: Facing) -> Rect {
+    let feet = player_rect(player_transform);
+    let (x0, y0, x1, y1) = (feet.x0 as f32, feet.y0 as f32, feet.x1 as f32, feet.y1 as f32);
+
+    match facing {
+        Facing::Up => Rect::new(x0, y1, x1, y1 + REACH),
+        Facing::Down => Rect::new(x0, y0 - REACH, x1, y0),
+        Facing::Left => Rect::new(x0 - REACH, y0, x0, y1),
+        Facing::Right => Rect::new(x1, y0, x1 + REACH, y1),
+    }
+}
+
+///A dependancy with a space in it is a condition on the WorldState, anything else is an interactable id
+fn dependancy_condition(dependancy: &str) -> Option<Result<Condition, String>> {
+    dependancy
+        .contains(char::is_whitespace)
+        .then(|| script::parse_condition(dependancy))
+}
+
+///Whether every dependancy of `interactable` is met: the interactables it names have been used at least once
+/// and its conditions hold, `counts` has the interaction count of every loaded interactable
+/// and `remembered` covers the ones in rooms that are not loaded right now
+fn dependancies_met(
+    interactable: &Interactable,
+    counts: &HashMap<Entity, u32>,
+    registry: &InteractableRegistry,
+    remembered: &LevelMemory,
+    world_state: &WorldState,
+    inventory: Option<&Inventory>,
+) -> bool {
+    interactable.dependancies.iter().all(|dependancy| match dependancy_condition(dependancy) {
+        Some(condition) => condition.is_ok_and(|condition| condition.holds(world_state, inventory)),
+        None => match registry.get(dependancy) {
+            Some(entity) => counts.get(&entity).is_some_and(|count| *count > 0),
+            None => remembered.interaction_count(dependancy) > 0,
+        },
+    })
+}
+
+///What is in front of the player
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Target {
+    Usable(Entity),
+
+    //in reach and facing the right way, but its dependancies are not met yet
+    Locked(Entity),
+}
+
+///Finds what is under the probe and can be used from this side, closest to the probe wins
+/// anything usable right now is picked over something locked that is closer
+fn find_target(
+    probe: Rect,
+    facing: Facing,
+    interactables: &[(Entity, &Interactable)],
+    registry: &InteractableRegistry,
+    remembered: &LevelMemory,
+    world_state: &WorldState,
+    inventory: Option<&Inventory>,
+) -> Option<Target> {
+    let counts: HashMap<Entity, u32> = interactables
+        .iter()
+        .map(|(entity, interactable)| (*entity, interactable.interaction_count))
+        .collect();
+
+    let in_reach = interactables
+        .iter()
+        .filter(|(_, interactable)| !probe.intersect(interactable.boundary).is_empty())
+        .filter(|(_, interactable)| interactable.can_interact_from(facing));
+
+    let nearest = |candidates: &mut dyn Iterator<Item = &(Entity, &Interactable)>| {
+        candidates
+            .min_by(|(_, a), (_, b)| {
+                let a = a.boundary.center().distance_squared(probe.center());
+                let b = b.boundary.center().distance_squared(probe.center());
+                a.total_cmp(&b)
+            })
+            .map(|(entity, _)| *entity)
+    };
+
+    let mut usable = in_reach
+        .clone()
+        .filter(|(_, interactable)| dependancies_met(interactable, &counts, registry, remembered, world_state, inventory));
+
+    nearest(&mut usable)
+        .map(Target::Usable)
+        .or_else(|| nearest(&mut in_reach.clone()).map(Target::Locked))
+}
+
+///Picks what the player would interact with, only ever something whose dependancies are met
+fn pick_target(
+    probe: Rect,
+    facing: Facing,
+    interactables: &[(Entity, &Interactable)],
+    registry: &InteractableRegistry,
+    remembered: &LevelMemory,
+    world_state: &WorldState,
+    inventory: Option<&Inventory>,
+) -> Option<Entity> {
+    match find_target(probe, facing, interactables, registry, remembered, world_state, inventory) {
+        Some(Target::Usable(entity)) => Some(entity),
+        _ => None,
+    }
+}
+
+///Interacts with whatever is in front of the player when Interact is pressed
+pub fn interact_with_objects(
+    actions: Res<ActionState>,
+    players: Query<(&Transform, &Facing, Option<&Inventory>), (With<Player>, Without<Shadow>)>,
+    mut interactables: Query<(Entity, &mut Interactable)>,
+    (registry, memory, current_level): (Res<InteractableRegistry>, Res<InteractableMemory>, Res<CurrentLevel>),
+    world_state: Res<WorldState>,
+    mut events: EventWriter<InteractionEvent>,
+) {
+    if !actions.just_pressed(GameAction::Interact) {
+        return;
+    }
+
+    let nothing_remembered = LevelMemory::default();
+    let remembered = memory.level(current_level.0).unwrap_or(&nothing_remembered);
+
+    for (player_transform, &facing, inventory) in &players {
+        let probe = interaction_probe(player_transform, facing);
+
+        let candidates: Vec<_> = interactables.iter().collect();
+        let Some(target) = pick_target(probe, facing, &candidates, &registry, remembered, &world_state, inventory) else {
+            continue;
+        };
+
+        if let Ok((_, mut interactable)) = interactables.get_mut(target) {
+            interactable.interact();
+            events.send(InteractionEvent {
+                entity: target,
+                interaction_count: interactable.interaction_count,
+                facing,
+            });
+        }
+    }
+}
+
+//Tag component for the text floating over whatever the player could interact with
+#[derive(Component)]
+struct InteractionPrompt;
+
+fn spawn_interaction_prompt(mut commands: Commands) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", TextStyle { font_size: 20.0, color: PROMPT_COLOR, ..default() }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        InteractionPrompt,
+    ));
+}
+
+///Floats a prompt over the interactable in front of the player, or a locked one if its dependancies are not met
+/// hidden whenever interacting is not possible
+fn update_interaction_prompt(
+    players: Query<(&Transform, &Facing, Option<&Inventory>), (With<Player>, Without<Shadow>)>,
+    interactables: Query<(Entity, &Interactable)>,
+    mut prompts: Query<(&mut Text, &mut Transform, &mut Visibility), (With<InteractionPrompt>, Without<Player>)>,
+    (registry, memory, current_level): (Res<InteractableRegistry>, Res<InteractableMemory>, Res<CurrentLevel>),
+    world_state: Res<WorldState>,
+    (bindings, gamepads): (Res<InputBindings>, Res<Gamepads>),
+    (dialogue, inventory_open, transition): (Option<Res<ActiveDialogue>>, Option<Res<InventoryOpen>>, Option<Res<RoomTransition>>),
+) {
+    let Ok((mut text, mut transform, mut visibility)) = prompts.get_single_mut() else {
+        return;
+    };
+
+    let nothing_remembered = LevelMemory::default();
+    let remembered = memory.level(current_level.0).unwrap_or(&nothing_remembered);
+
+    let busy = dialogue.is_some() || inventory_open.is_some() || transition.is_some();
+    let target = players.get_single().ok().filter(|_| !busy).and_then(|(player_transform, &facing, inventory)| {
+        let probe = interaction_probe(player_transform, facing);
+        let candidates: Vec<_> = interactables.iter().collect();
+        find_target(probe, facing, &candidates, &registry, remembered, &world_state, inventory)
+    });
+
+    let Some(target) = target else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+
+    let (entity, message, color) = match target {
+        Target::Usable(entity) => {
+            //show the button for whatever the player is most likely holding
+            let on_gamepad = gamepads.iter().next().is_some();
+            let binding = bindings
+                .bindings_for(GameAction::Interact)
+                .iter()
+                .find(|binding| matches!(binding, Binding::Gamepad(_)) == on_gamepad);
+            let message = match binding {
+                Some(binding) => format!("[{}] Inspect", binding.label()),
+                None => "Inspect".to_string(),
+            };
+            (entity, message, PROMPT_COLOR)
+        }
+        Target::Locked(entity) => (entity, "Locked".to_string(), LOCKED_PROMPT_COLOR),
+    };
+
+    let Ok((_, interactable)) = interactables.get(entity) else {
+        return;
+    };
+
+    text.sections[0].value = message;
+    text.sections[0].style.color = color;
+    transform.translation = Vec3::new(interactable.boundary.center().x, interactable.boundary.max.y + 14.0, 60.0);
+    visibility.set_if_neq(Visibility::Inherited);
+}
+
+fn hide_interaction_prompt(mut prompts: Query<&mut Visibility, With<InteractionPrompt>>) {
+    for mut visibility in &mut prompts {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn update_interactable_registry(
+    added: Query<(Entity, &Interactable), Added<Interactable>>,
+    mut removed: RemovedComponents<Interactable>,
+    mut registry: ResMut<InteractableRegistry>,
+) {
+    for entity in removed.read() {
+        registry.0.retain(|_, registered| *registered != entity);
+    }
+
+    for (entity, interactable) in &added {
+        if interactable.id.is_empty() {
+            continue;
+        }
+        if let Some(previous) = registry.0.insert(interactable.id.clone(), entity) {
+            warn!("Interactable id `{}` is used by both {} and {}", interactable.id, previous, entity);
+        }
+    }
+}
+
+///Writes down every change to an interactable as it happens, so despawning its room loses nothing
+fn remember_interactables(
+    interactables: Query<Ref<Interactable>, Changed<Interactable>>,
+    current_level: Res<CurrentLevel>,
+    mut memory: ResMut<InteractableMemory>,
+) {
+    for interactable in &interactables {
+        //fresh from the file, there is nothing new to remember yet
+        if interactable.is_added() {
+            continue;
+        }
+        memory.remember(current_level.0, &interactable);
+    }
+}
+
+fn log_interactions(mut events: EventReader<InteractionEvent>) {
+    for event in events.read() {
+        info!("Interacted with {} facing {:?} ({} times)", event.entity, event.facing, event.interaction_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn box_at(x: f32, y: f32) -> Interactable {
+        Interactable::new(
+            String::new(),
+            Rect::new(x, y, x + PIXEL_SCALE, y + PIXEL_SCALE),
+            vec![Facing::Up, Facing::Down, Facing::Left, Facing::Right],
+        )
+    }
+
+    fn probe() -> Rect {
+        interaction_probe(&Transform::from_scale(Vec3::splat(PIXEL_SCALE)), Facing::Right)
+    }
+
+    #[test]
+    fn facing_follows_the_strongest_direction() {
+        assert_eq!(Facing::from_direction(Vec2::new(0.2, -0.9)), Some(Facing::Down));
+        assert_eq!(Facing::from_direction(Vec2::new(-1.0, 1.0)), Some(Facing::Left));
+        assert_eq!(Facing::from_direction(Vec2::ZERO), None);
+    }
+
+    #[test]
+    fn only_reaches_what_is_in_front() {
+        let ahead = box_at(PIXEL_SCALE * 0.7, 0.0);
+        let behind = box_at(-PIXEL_SCALE * 2.0, 0.0);
+        let list = [(Entity::from_raw(1), &ahead), (Entity::from_raw(2), &behind)];
+
+        assert_eq!(pick_target(probe(), Facing::Right, &list, &InteractableRegistry::default(), &LevelMemory::default(), &WorldState::default(), None), Some(Entity::from_raw(1)));
+        assert_eq!(pick_target(probe(), Facing::Right, &list[1..], &InteractableRegistry::default(), &LevelMemory::default(), &WorldState::default(), None), None);
+    }
+
+    #[test]
+    fn respects_valid_directions() {
+        let mut from_above_only = box_at(PIXEL_SCALE * 0.7, 0.0);
+        from_above_only.valid_directions = vec![Facing::Down];
+
+        assert_eq!(pick_target(probe(), Facing::Right, &[(Entity::from_raw(1), &from_above_only)], &InteractableRegistry::default(), &LevelMemory::default(), &WorldState::default(), None), None);
+    }
+
+    #[test]
+    fn waits_for_dependancies() {
+        let registry = InteractableRegistry(HashMap::from([("lever".to_string(), Entity::from_raw(1))]));
+        let lever = box_at(-PIXEL_SCALE * 4.0, 0.0);
+        let mut door = box_at(PIXEL_SCALE * 0.7, 0.0);
+        door.dependancies.push("lever".to_string());
+
+        let list = [(Entity::from_raw(1), &lever), (Entity::from_raw(2), &door)];
+        assert_eq!(pick_target(probe(), Facing::Right, &list, &registry, &LevelMemory::default(), &WorldState::default(), None), None);
+
+        let mut pulled = box_at(-PIXEL_SCALE * 4.0, 0.0);
+        pulled.interact();
+        let list = [(Entity::from_raw(1), &pulled), (Entity::from_raw(2), &door)];
+        assert_eq!(pick_target(probe(), Facing::Right, &list, &registry, &LevelMemory::default(), &WorldState::default(), None), Some(Entity::from_raw(2)));
+    }
+
+    #[test]
+    fn remembers_interactables_in_unloaded_rooms() {
+        let mut lever = box_at(-PIXEL_SCALE * 4.0, 0.0);
+        lever.id = "lever".to_string();
+        lever.interact();
+
+        let mut memory = InteractableMemory::default();
+        memory.remember(1, &lever);
+
+        //the lever's room is gone, the door still knows it was pulled
+        let mut door = box_at(PIXEL_SCALE * 0.7, 0.0);
+        door.dependancies.push("lever".to_string());
+        let list = [(Entity::from_raw(2), &door)];
+        let registry = InteractableRegistry::default();
+        let remembered = memory.level(1).unwrap();
+        assert_eq!(pick_target(probe(), Facing::Right, &list, &registry, remembered, &WorldState::default(), None), Some(Entity::from_raw(2)));
+
+        //and the lever comes back pulled
+        let mut respawned = box_at(-PIXEL_SCALE * 4.0, 0.0);
+        respawned.id = "lever".to_string();
+        memory.restore(1, &mut respawned);
+        assert_eq!(respawned.interaction_count, 1);
+
+        memory.restore(2, &mut door);
+        assert_eq!(door.dependancies, vec!["lever".to_string()]);
+    }
+
+    #[test]
+    fn locked_things_are_still_found() {
+        let mut door = box_at(PIXEL_SCALE * 0.7, 0.0);
+        door.dependancies.push("has bedroom_key".to_string());
+        let list = [(Entity::from_raw(1), &door)];
+        let registry = InteractableRegistry::default();
+
+        assert_eq!(
+            find_target(probe(), Facing::Right, &list, &registry, &LevelMemory::default(), &WorldState::default(), None),
+            Some(Target::Locked(Entity::from_raw(1)))
+        );
+
+        //something usable further away still wins over the locked door
+        let drawer = box_at(PIXEL_SCALE * 0.9, 0.0);
+        let list = [(Entity::from_raw(1), &door), (Entity::from_raw(2), &drawer)];
+        assert_eq!(
+            find_target(probe(), Facing::Right, &list, &registry, &LevelMemory::default(), &WorldState::default(), None),
+            Some(Target::Usable(Entity::from_raw(2)))
+        );
+    }
+
+    #[test]
+    fn can_depend_on_world_state() {
+        let mut drawer = box_at(PIXEL_SCALE * 0.7, 0.0);
+        drawer.dependancies.push("flag lights_on".to_string());
+        let list = [(Entity::from_raw(1), &drawer)];
+        let registry = InteractableRegistry::default();
+
+        let mut world_state = WorldState::default();
+        assert_eq!(pick_target(probe(), Facing::Right, &list, &registry, &LevelMemory::default(), &world_state, None), None);
+
+        world_state.set("lights_on", crate::game::world_state::FlagValue::Bool(true));
+        assert_eq!(pick_target(probe(), Facing::Right, &list, &registry, &LevelMemory::default(), &world_state, None), Some(Entity::from_raw(1)));
+    }
+
+    #[test]
+    fn validation_reports_missing_ids_and_cycles() {
+        let named = |id: &str, dependancies: &[&str]| {
+            let mut interactable = box_at(0.0, 0.0);
+            interactable.id = id.to_string();
+            interactable.dependancies = dependancies.iter().map(|d| d.to_string()).collect();
+            interactable
+        };
+
+        let errors = validate_dependancies(&[
+            named("a", &["b"]),
+            named("b", &["c"]),
+            named("c", &["a"]),
+            named("door", &["key"]),
+            named("door", &[]),
+            named("lamp", &["flag power_on", "flag"]),
+            named("safe", &["coins ~ 3"]),
+        ]);
+
+        assert_eq!(errors, vec![
+            DependancyError::DuplicateId("door".to_string()),
+            DependancyError::MissingId { interactable: "door".to_string(), missing: "key".to_string() },
+            DependancyError::MissingId { interactable: "lamp".to_string(), missing: "flag".to_string() },
+            DependancyError::BadCondition {
+                interactable: "safe".to_string(),
+                condition: "coins ~ 3".to_string(),
+                message: "`~` is not a comparison, use one of == != < <= > >=".to_string(),
+            },
+            DependancyError::Cycle(vec!["a", "b", "c", "a"].into_iter().map(String::from).collect()),
+        ]);
+    }
+}
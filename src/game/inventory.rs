@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::input::{ActionState, GameAction};
+use crate::resources::{GameState, PlayState};
+
+use super::dialogue::ActiveDialogue;
+use super::{Player, Shadow};
+
+///Every item in the game is described here
+pub const ITEMS_PATH: &str = "assets/config/items.json";
+
+const PANEL_COLOR: Color = Color::srgba(0.05, 0.05, 0.1, 0.95);
+const NAME_COLOR: Color = Color::srgb(0.95, 0.8, 0.4);
+const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+
+pub fn inventory_plugin(app: &mut App) {
+    let items = match ItemDatabase::load(ITEMS_PATH) {
+        Ok(items) => items,
+        Err(err) => {
+            warn!("Could not load {}, there will be no items: {}", ITEMS_PATH, err);
+            ItemDatabase::default()
+        }
+    };
+
+    app
+        .insert_resource(items)
+        .init_resource::<CarriedInventory>()
+        .add_systems(Update, (
+            toggle_inventory_screen
+                .run_if(in_state(PlayState::Playing))
+                .run_if(not(resource_exists::<ActiveDialogue>)),
+            refresh_inventory_screen.run_if(resource_exists::<InventoryOpen>),
+        ).chain().run_if(in_state(GameState::Running)))
+
+        //the screen does not survive leaving the game
+        .add_systems(OnExit(GameState::Running), close_inventory_screen);
+}
+
+///What an item is, as written in ITEMS_PATH
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+
+    //asset path of the icon shown in the inventory screen
+    pub icon: String,
+
+    #[serde(default)]
+    pub description: String,
+}
+
+///All item definitions, in the order they are listed in the file
+#[derive(Resource, Debug, Default)]
+pub struct ItemDatabase(Vec<ItemDefinition>);
+
+impl ItemDatabase {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file_content = fs::read_to_string(path)?;
+        Ok(ItemDatabase(serde_json::from_str(&file_content)?))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+        self.0.iter().find(|item| item.id == id)
+    }
+}
+
+///Items the player is carrying and how many of each
+#[derive(Component, Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inventory(BTreeMap<String, u32>);
+
+impl Inventory {
+    pub fn count(&self, item: &str) -> u32 {
+        self.0.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, item: &str) {
+        *self.0.entry(item.to_string()).or_insert(0) += 1;
+    }
+
+    ///Takes one of `item` away, false if there was none to take
+    pub fn remove(&mut self, item: &str) -> bool {
+        match self.0.get_mut(item) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.0.remove(item);
+            }
+            None => return false,
+        }
+        true
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &u32)> {
+        self.0.iter()
+    }
+}
+
+///Holds on to the player's inventory while a level is loading, the player entity does not survive that
+/// create_game_objects gives it to the new player
+#[derive(Resource, Debug, Default)]
+pub struct CarriedInventory(pub Inventory);
+
+///Exists while the inventory screen is open, player movement and interaction are paused for as long as it is around
+#[derive(Resource, Debug)]
+pub struct InventoryOpen;
+
+//Tag components for the inventory screen
+#[derive(Component)]
+struct InventoryScreen;
+
+#[derive(Component)]
+struct InventoryList;
+
+///Copies the player's inventory somewhere safe, runs before the player is despawned for a level load
+pub fn stash_inventory(players: Query<&Inventory, (With<Player>, Without<Shadow>)>, mut carried: ResMut<CarriedInventory>) {
+    for inventory in &players {
+        carried.0 = inventory.clone();
+    }
+}
+
+fn toggle_inventory_screen(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    screens: Query<Entity, With<InventoryScreen>>,
+) {
+    if !actions.just_pressed(GameAction::Inventory) {
+        return;
+    }
+
+    if !screens.is_empty() {
+        for entity in &screens {
+            commands.entity(entity).despawn_recursive();
+        }
+        commands.remove_resource::<InventoryOpen>();
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                z_index: ZIndex::Global(40),
+                ..default()
+            },
+            InventoryScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        min_width: Val::Px(480.0),
+                        padding: UiRect::all(Val::Px(20.0)),
+                        row_gap: Val::Px(12.0),
+                        ..default()
+                    },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Inventory",
+                        TextStyle { font_size: 36.0, color: TEXT_COLOR, ..default() },
+                    ));
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(8.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        InventoryList,
+                    ));
+                });
+        });
+    commands.insert_resource(InventoryOpen);
+}
+
+///Rebuilds the list of items whenever the screen opens or the inventory changes under it
+fn refresh_inventory_screen(
+    mut commands: Commands,
+    lists: Query<Entity, Added<InventoryList>>,
+    all_lists: Query<Entity, With<InventoryList>>,
+    players: Query<Ref<Inventory>, (With<Player>, Without<Shadow>)>,
+    items: Res<ItemDatabase>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(inventory) = players.get_single() else {
+        return;
+    };
+
+    let targets: Vec<Entity> = if inventory.is_changed() { all_lists.iter().collect() } else { lists.iter().collect() };
+
+    for list in targets {
+        commands.entity(list).despawn_descendants().with_children(|parent| {
+            if inventory.iter().next().is_none() {
+                parent.spawn(TextBundle::from_section(
+                    "Nothing here yet.",
+                    TextStyle { font_size: 24.0, color: TEXT_COLOR, ..default() },
+                ));
+            }
+
+            for (id, count) in inventory.iter() {
+                //items missing from the database still show up, just without the nice parts
+                let (name, icon, description) = match items.get(id) {
+                    Some(item) => (item.name.clone(), Some(item.icon.clone()), item.description.clone()),
+                    None => (id.clone(), None, String::new()),
+                };
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(12.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        if let Some(icon) = icon {
+                            parent.spawn(ImageBundle {
+                                style: Style {
+                                    width: Val::Px(32.0),
+                                    height: Val::Px(32.0),
+                                    ..default()
+                                },
+                                image: UiImage::new(asset_server.load(icon)),
+                                ..default()
+                            });
+                        }
+
+                        let label = if *count > 1 { format!("{name} x{count}") } else { name };
+                        parent.spawn(TextBundle::from_sections([
+                            TextSection::new(label, TextStyle { font_size: 26.0, color: NAME_COLOR, ..default() }),
+                            TextSection::new(
+                                format!("\n{description}"),
+                                TextStyle { font_size: 20.0, color: TEXT_COLOR, ..default() },
+                            ),
+                        ]));
+                    });
+            }
+        });
+    }
+}
+
+fn close_inventory_screen(mut commands: Commands, screens: Query<Entity, With<InventoryScreen>>) {
+    for entity in &screens {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<InventoryOpen>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_items_in_and_out() {
+        let mut inventory = Inventory::default();
+        inventory.add("coin");
+        inventory.add("coin");
+
+        assert_eq!(inventory.count("coin"), 2);
+        assert!(inventory.remove("coin"));
+        assert!(inventory.remove("coin"));
+        assert!(!inventory.remove("coin"));
+        assert_eq!(inventory.iter().count(), 0);
+    }
+
+    #[test]
+    fn item_file_loads() {
+        let items = ItemDatabase::load(ITEMS_PATH).unwrap();
+        assert!(items.get("bedroom_key").is_some());
+    }
+}
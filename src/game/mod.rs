@@ -1,39 +1,66 @@
 use bevy::a11y::accesskit::Rect;
 use bevy::prelude::*;
-use bevy::reflect::serde::ReflectDeserializer;
-use bevy::reflect::{GetTypeRegistration, TypeRegistry};
 use bevy::sprite::Anchor;
-use serde::de::{DeserializeOwned, DeserializeSeed};
-use serde::{Deserialize, Serialize};
-use serde_json::Deserializer;
 
-use std::error::Error;
-use std::fs::{self, File};
-use std::io::{self, BufRead};
-use std::path::Path;
+use log::debug;
 
-use log::{debug, warn};
-
-use crate::{IS_IN_WINDOWS, PIXEL_SCALE};
+use crate::input::{ActionState, GameAction};
+use crate::{despawn_screen, IS_IN_WINDOWS, PIXEL_SCALE};
 
 use super::resources::*;
 
-mod rooms;
+mod broadphase;
+pub mod collider_merge;
+mod cutscene;
+mod debug_overlay;
+mod dialogue;
+pub mod interactable_sprite;
+pub mod interactables_file;
+pub mod inventory;
+mod movement;
+pub mod rooms;
+mod room_manifest;
+pub mod script;
+pub mod svg;
+mod sweep;
+mod transition;
+pub mod world_state;
 pub mod interaction;
 
 pub fn game_plugin(app: &mut App) {
     app
     
         .insert_resource(Time::<Fixed>::from_hz(64.0))
+        .init_resource::<broadphase::ColliderGrid>()
+        .add_sub_state::<PlayState>()
         .add_plugins(rooms::room_plugin)
+        .add_plugins(transition::transition_plugin)
+        .add_plugins(movement::movement_plugin)
+        .add_plugins(interaction::interaction_plugin)
+        .add_plugins(dialogue::dialogue_plugin)
+        .add_plugins(world_state::world_state_plugin)
+        .add_plugins(inventory::inventory_plugin)
+        .add_plugins(interactable_sprite::interactable_sprite_plugin)
+        .add_plugins(cutscene::cutscene_plugin)
+        .add_plugins(debug_overlay::debug_overlay_plugin)
         
-        .add_systems(OnEnter(GameState::LevelLoading), (create_game_objects, rooms::load_level_room_data).chain())
+        //anything left over from the previous level goes before the new one is built
+        .add_systems(OnEnter(GameState::LevelLoading), (
+            inventory::stash_inventory,
+            despawn_screen::<Player>,
+            despawn_screen::<interaction::Interactable>,
+            create_game_objects,
+            rooms::load_level_room_data,
+        ).chain())
 
         .add_systems(FixedUpdate, (
-            player_movement,
+            player_movement
+                .run_if(not(resource_exists::<transition::RoomTransition>))
+                .run_if(not(resource_exists::<dialogue::ActiveDialogue>))
+                .run_if(not(resource_exists::<inventory::InventoryOpen>)),
             collision_detection,
             move_camera,
-        ).run_if(in_state(GameState::Running)));
+        ).run_if(in_state(PlayState::Playing)));
 }
 
 //Component Used to tag the player and give it velocity
@@ -47,18 +74,34 @@ pub struct Player {
 #[derive(Component)]
 pub struct Shadow;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Component, Clone, Copy, PartialEq, Debug)]
-enum ColliderType {
+pub enum ColliderType {
     RIGID,
     Interactable,
     ChangeRoom,
 }
 
 ///Transform and style of a collider
-#[derive(Component, Clone, Copy, Debug)]
+/// ChangeRoom colliders may also know where they lead
+#[derive(Component, Clone, Debug)]
 struct Collider {
     transform: Transform,
     style: ColliderType,
+    destination: Option<transition::Destination>,
+}
+
+impl Collider {
+    ///The world space rect this collider covers, colliders are anchored at their top left
+    fn rect(&self) -> Rect {
+        let c_left: f64 = self.transform.translation.x as f64; //left side of collider on x
+        let c_right: f64 = c_left + self.transform.scale.x as f64; //right side of collider calculated from left side and size
+
+        let c_top: f64 = self.transform.translation.y as f64; //top of collider
+        let c_bot: f64 = c_top - self.transform.scale.y as f64; //bottom of collider calculated from top and size
+
+        Rect::new(c_left, c_bot, c_right, c_top)
+    }
 }
 
 
@@ -76,15 +119,14 @@ fn move_camera(
 
 fn create_game_objects(
     mut commands: Commands, 
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    carried_inventory: Res<inventory::CarriedInventory>,
 ) {
-    let tex;
-
-    if IS_IN_WINDOWS {
-        tex = asset_server.load("textures\\player\\player_singlet.png");
+    let tex = if IS_IN_WINDOWS {
+        asset_server.load("textures\\player\\player_singlet.png")
     }else {
-        tex = asset_server.load("textures/player/player_singlet.png");
-    }
+        asset_server.load("textures/player/player_singlet.png")
+    };
 
     commands.spawn((
         SpriteBundle {
@@ -113,16 +155,18 @@ fn create_game_objects(
             vel_x: 0.0,
             vel_y: 0.0,
         },
+        interaction::Facing::Down,
+        carried_inventory.0.clone(),
+        //sorted by their feet, against anything else standing in the room
+        interactable_sprite::YSort,
     ));
     info!("Created player");
 
-    let tex;
-
-    if IS_IN_WINDOWS {
-        tex = asset_server.load("textures\\player\\player_shadow.png");
+    let tex = if IS_IN_WINDOWS {
+        asset_server.load("textures\\player\\player_shadow.png")
     }else {
-        tex = asset_server.load("textures/player/player_shadow.png");
-    }
+        asset_server.load("textures/player/player_shadow.png")
+    };
 
     commands.spawn(
         (
@@ -135,7 +179,7 @@ fn create_game_objects(
                 texture: tex,
                 transform: Transform {
                     translation: Vec3 {
-                        x: -1.0 * (PIXEL_SCALE * 0.125),
+                        x: -(PIXEL_SCALE * 0.125),
                         z: 10.5,
                         ..default()
                     },
@@ -157,64 +201,64 @@ fn create_game_objects(
     );
     info!("Created shadow");
 
-    //this doesnt work at the top of this function because of some borrowing issue, i want to learn why some day
-    new_spawn_something::<interaction::Interactable>(commands, "assets/textures/rooms/L1/interactables.json").unwrap();
-    info!("Created something");
 }
 
+///The part of the player that collides with things, a thin strip at their feet
+fn player_rect(player_transform: &Transform) -> Rect {
+    let px_scale: f64 = (PIXEL_SCALE * 0.625) as f64;
+    let p_left: f64 = player_transform.translation.x as f64;
+    let p_right: f64 = p_left + px_scale;
+
+    let p_bot: f64 = player_transform.translation.y as f64;
+    let py_scale: f64 = player_transform.scale.y as f64 * 0.2;
+    let p_top: f64 = p_bot + py_scale;
+
+    Rect::new(p_left, p_bot, p_right, p_top)
+}
+
+///Pushes the player back out of anything they ended up inside of (spawning in a wall, float rounding)
+/// player_movement already sweeps the player so normal movement should never need this
 fn collision_detection(
     mut player: Query<(&mut Transform, &mut Player), Without<Shadow>>,
     colliders: Query<&Collider, Without<Player>>,
+    collider_grid: Res<broadphase::ColliderGrid>,
 ) {
     for (mut player_transform, _) in &mut player {
         //create a rect containing the current location of the player
-
-        let px_scale: f64 = (PIXEL_SCALE * 0.625) as f64;
-        let p_left: f64 = player_transform.translation.x as f64;
-        let p_right: f64 = p_left + px_scale;
-
-        let p_bot: f64 = player_transform.translation.y as f64;
-        let py_scale: f64 = player_transform.scale.y as f64 * 0.2;
-        let p_top: f64 = p_bot + py_scale;
-
-        let p_rect = Rect::new(p_left, p_bot, p_right, p_top);
-
-        for collider in &colliders {
+        let p_rect = player_rect(&player_transform);
+        let px_scale: f64 = p_rect.width();
+        let py_scale: f64 = p_rect.height();
+
+        //only look at colliders the broadphase says are near the player
+        for entity in collider_grid.query(p_rect) {
+            let Ok(collider) = colliders.get(entity) else {
+                continue;
+            };
             //we need to check if the player is inside this collider, if so we need to push them outside of it
 
             //create a rect to test against the player rect
-            let cx_scale: f64 = collider.transform.scale.x as f64; //size of collider
-            let c_left: f64 = collider.transform.translation.x as f64; //left side of collider on x
-            let c_right: f64 = collider.transform.translation.x as f64 + cx_scale; //right side of collider calculated from left side and size
-
-            let c_top: f64 = collider.transform.translation.y as f64; //top of collider
-            let cy_scale: f64 = collider.transform.scale.y as f64; //size of collider on y
-            let c_bot: f64 = collider.transform.translation.y as f64 - cy_scale; //bottom of collider calculated from top and size
-
-            let c_rect = Rect::new(c_left, c_bot, c_right, c_top);
+            let c_rect = collider.rect();
 
             debug!("c_rect: {c_rect:?}");
             debug!("p_rect: {p_rect:?}");
 
-            if p_rect.intersect(c_rect).area() != 0.0 {
-                if collider.style == ColliderType::RIGID {
-                    debug!("INTERSECTION DETECTED!");
-                    let intersection = p_rect.intersect(c_rect);
-
-                    if intersection.width() < intersection.height() {
-                        if p_rect.min_x() < c_rect.min_x() {
-                            player_transform.translation.x =
-                                c_rect.min_x() as f32 - px_scale as f32;
-                        } else if p_rect.max_x() > c_rect.max_x() {
-                            player_transform.translation.x = c_rect.max_x() as f32;
-                        }
-                    } else if intersection.width() > intersection.height() {
-                        if p_rect.min_y() < c_rect.min_y() {
-                            player_transform.translation.y =
-                                c_rect.min_y() as f32 - py_scale as f32;
-                        } else if p_rect.max_y() > c_rect.max_y() {
-                            player_transform.translation.y = c_rect.max_y() as f32;
-                        }
+            if p_rect.intersect(c_rect).area() != 0.0 && collider.style == ColliderType::RIGID {
+                debug!("INTERSECTION DETECTED!");
+                let intersection = p_rect.intersect(c_rect);
+
+                if intersection.width() < intersection.height() {
+                    if p_rect.min_x() < c_rect.min_x() {
+                        player_transform.translation.x =
+                            c_rect.min_x() as f32 - px_scale as f32;
+                    } else if p_rect.max_x() > c_rect.max_x() {
+                        player_transform.translation.x = c_rect.max_x() as f32;
+                    }
+                } else if intersection.width() > intersection.height() {
+                    if p_rect.min_y() < c_rect.min_y() {
+                        player_transform.translation.y =
+                            c_rect.min_y() as f32 - py_scale as f32;
+                    } else if p_rect.max_y() > c_rect.max_y() {
+                        player_transform.translation.y = c_rect.max_y() as f32;
                     }
                 }
             }
@@ -223,35 +267,70 @@ fn collision_detection(
 }
 
 fn player_movement(
-    mut players: Query<(&mut Transform, &mut Player, &mut Sprite), Without<Shadow>>,
-    
+    mut players: Query<(&mut Transform, &mut Player, &mut Sprite, &mut interaction::Facing), Without<Shadow>>,
+
     mut shadow_transform: Query<&mut Transform, With<Shadow>>,
-    input: Res<ButtonInput<KeyCode>>,
+    colliders: Query<&Collider>,
+    collider_grid: Res<broadphase::ColliderGrid>,
+    movement_config: Res<movement::MovementConfig>,
+    actions: Res<ActionState>,
     time: Res<Time>,
 ) {
-    for (mut transform, mut player, mut sprite) in &mut players {
-        if input.pressed(KeyCode::ArrowUp) && !input.pressed(KeyCode::ArrowDown) {
-            player.vel_y = 120.0;
-        }
-        if input.pressed(KeyCode::ArrowDown) && !input.pressed(KeyCode::ArrowUp) {
-            player.vel_y = -120.0;
-        }
-        if input.pressed(KeyCode::ArrowRight) && !input.pressed(KeyCode::ArrowLeft) {
-            player.vel_x = 150.0;
+    for (mut transform, mut player, mut sprite, mut facing) in &mut players {
+        //keys and d-pad give whole directions, the left stick can give anything in between
+        let direction = actions.movement();
+
+        if direction.x > 0.0 {
             sprite.flip_x = false;
-        }
-        if input.pressed(KeyCode::ArrowLeft) && !input.pressed(KeyCode::ArrowRight) {
-            player.vel_x = -150.0;
+        } else if direction.x < 0.0 {
             sprite.flip_x = true;
         }
 
-        //apply velocity
-        transform.translation.y += player.vel_y * time.delta_seconds();
-        transform.translation.x += player.vel_x * time.delta_seconds();
+        //keep facing the last way we moved once the input is let go
+        if let Some(new_facing) = interaction::Facing::from_direction(direction) {
+            facing.set_if_neq(new_facing);
+        }
 
-        //apply friction
-        player.vel_y = player.vel_y * 0.99 as i32 as f32;
-        player.vel_x = player.vel_x * 0.99 as i32 as f32;
+        //accelerate towards the speed for what is held, or slow down when nothing is
+        let target = movement_config.target_velocity(
+            direction,
+            actions.pressed(GameAction::Sprint),
+            actions.pressed(GameAction::Sneak),
+        );
+        let velocity = movement_config.step_velocity(
+            Vec2::new(player.vel_x, player.vel_y),
+            target,
+            time.delta_seconds(),
+        );
+        player.vel_x = velocity.x;
+        player.vel_y = velocity.y;
+
+        //apply velocity, sweeping the player's hitbox so a long frame can not carry it through a wall
+        let delta = (
+            (player.vel_x * time.delta_seconds()) as f64,
+            (player.vel_y * time.delta_seconds()) as f64,
+        );
+        let p_rect = player_rect(&transform);
+
+        let obstacles: Vec<Rect> = collider_grid
+            .query(sweep::swept_bounds(p_rect, delta))
+            .into_iter()
+            .filter_map(|entity| colliders.get(entity).ok())
+            .filter(|collider| collider.style == ColliderType::RIGID)
+            .map(Collider::rect)
+            .collect();
+
+        let (moved_x, moved_y) = sweep::move_and_slide(p_rect, delta, &obstacles);
+        transform.translation.x += moved_x as f32;
+        transform.translation.y += moved_y as f32;
+
+        //whatever we ran into eats the velocity going into it
+        if moved_x != delta.0 {
+            player.vel_x = 0.0;
+        }
+        if moved_y != delta.1 {
+            player.vel_y = 0.0;
+        }
 
         //move shadow to be under player
         for mut tf in &mut shadow_transform {
@@ -266,17 +345,6 @@ fn player_movement(
     }
 }
 
-///Helper function for loading files
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    warn!("Reading file: {:?}", filename.as_ref());
-
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
-}
-
 // #[derive(Debug, Serialize, Deserialize)]
 // pub struct InteractableStuff {
 //     action: String,
@@ -285,74 +353,3 @@ where
 //     interaction_count: u32,
 //     valid_directions: Vec<Directions>,
 // }
-
-
-fn new_spawn_something<T>(mut commands: Commands, path: &str) 
-    -> Result<(), Box<dyn Error>> 
-where 
-    T: DeserializeOwned + Component + TypePath + std::fmt::Debug
-{
-    // Read the file content into a string
-    let file_content = fs::read_to_string(path)?;
-    println!("File read to string...");
-
-    // Deserialize the JSON array into a Vec<T>
-    let items: Vec<T> = serde_json::from_str(&file_content)?;
-    println!("Obtained items: {:?}", items);
-
-    // Spawn each item in the `items` vector as a Bevy entity
-    for item in items {
-        println!("Item: {:?}", item);
-        commands.spawn(item);
-    }
-    
-    Ok(())
-}
-
-fn spawn_something<T>(mut commands: Commands, path: &str) 
-    -> Result<(), Box<dyn Error>> 
-        where T: for <'de>Deserialize<'de> + FromReflect + Component + TypePath + GetTypeRegistration + std::fmt::Debug
-{
-    let data = fs::read_to_string(path)?;
-    println!("file read to bytes...");
-
-    let mut registry = TypeRegistry::default();
-    registry.register::<T>();
-    registry.register::<Vec<T>>();
-
-    let mut deserializer = serde_json::Deserializer::from_str(&data);
-    println!("Deserializer created...");
-
-    let reflect_deserializer = ReflectDeserializer::new(&registry);
-    println!("Reflect Deserializer created...");
-
-    let output: Result<Box<dyn Reflect>, serde_json::Error> = reflect_deserializer.deserialize(&mut deserializer);
-    println!("output: {output:#?}");
-
-    let value: Vec<T> = <Vec<T> as FromReflect>::from_reflect(&*output?).unwrap();
-    println!("obtained value: {value:#?}");
-
-
-    for item in value {
-        println!("item: {item:#?}");
-        commands.spawn(item);
-    }
-    
-    Ok(())
-}
-
-fn spawn_interactable(mut commands: Commands) -> Result<(), Box<dyn Error>> {
-    let data = fs::read_to_string("assets/textures/rooms/L1/interactables.json")?;
-    let mut registry = TypeRegistry::default();
-    registry.register::<Vec<Interaction>>();
-    let mut deserializer = Deserializer::from_str(&data);
-    let reflect_deserializer = ReflectDeserializer::new(&registry);
-    let output: Box<dyn Reflect> = reflect_deserializer.deserialize(&mut deserializer).unwrap();
-    let value: Vec<Interaction> = <Vec<Interaction> as FromReflect>::from_reflect(&*output).unwrap();
-    for item in value {
-        println!("item: {item:#?}");
-        commands.spawn(item);
-    }
-    
-    Ok(())
-}
\ No newline at end of file
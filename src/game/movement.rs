@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fs;
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+///Where the movement tuning lives, edit it while the game is running and it will be picked up
+pub const MOVEMENT_CONFIG_PATH: &str = "assets/config/movement.json";
+
+pub fn movement_plugin(app: &mut App) {
+    let config = match MovementConfig::load(MOVEMENT_CONFIG_PATH) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!("Could not load {}, using default movement: {}", MOVEMENT_CONFIG_PATH, err);
+            MovementConfig::default()
+        }
+    };
+
+    app
+        .insert_resource(config)
+        .add_systems(Update, reload_movement_config);
+}
+
+///Tuning for how the player moves, all speeds are in pixels per second
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovementConfig {
+    //top walking speed
+    pub max_speed: f32,
+
+    //vertical speed is scaled by this so walking "into" the screen feels slower
+    pub vertical_scale: f32,
+
+    //how fast the player speeds up while a direction is held, per second
+    pub acceleration: f32,
+
+    //how fast the player slows to a stop once nothing is held, per second
+    pub deceleration: f32,
+
+    pub sprint_multiplier: f32,
+    pub sneak_multiplier: f32,
+
+    //stops diagonal movement from being faster than moving straight
+    pub normalize_diagonals: bool,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        MovementConfig {
+            max_speed: 150.0,
+            vertical_scale: 0.8,
+            acceleration: 1200.0,
+            deceleration: 1600.0,
+            sprint_multiplier: 1.5,
+            sneak_multiplier: 0.5,
+            normalize_diagonals: true,
+        }
+    }
+}
+
+impl MovementConfig {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file_content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&file_content)?)
+    }
+
+    ///The velocity the player should be heading towards for a held direction
+    /// `direction` is the raw input, each axis between -1 and 1
+    pub fn target_velocity(&self, direction: Vec2, sprinting: bool, sneaking: bool) -> Vec2 {
+        let mut direction = direction;
+        if self.normalize_diagonals && direction.length_squared() > 1.0 {
+            direction = direction.normalize();
+        }
+
+        let mut speed = self.max_speed;
+        if sprinting {
+            speed *= self.sprint_multiplier;
+        }
+        if sneaking {
+            speed *= self.sneak_multiplier;
+        }
+
+        Vec2::new(direction.x * speed, direction.y * speed * self.vertical_scale)
+    }
+
+    ///Moves `velocity` towards `target` by acceleration (or deceleration when stopping) over `delta_seconds`
+    pub fn step_velocity(&self, velocity: Vec2, target: Vec2, delta_seconds: f32) -> Vec2 {
+        let rate = if target == Vec2::ZERO { self.deceleration } else { self.acceleration };
+        let max_change = rate * delta_seconds;
+
+        let difference = target - velocity;
+        if difference.length() <= max_change {
+            target
+        } else {
+            velocity + difference.normalize() * max_change
+        }
+    }
+}
+
+///Checks the config file once a second and reloads it when it has been saved
+fn reload_movement_config(
+    mut config: ResMut<MovementConfig>,
+    mut last_modified: Local<Option<SystemTime>>,
+    mut check_timer: Local<Option<Timer>>,
+    time: Res<Time>,
+) {
+    let timer = check_timer.get_or_insert_with(|| Timer::from_seconds(1.0, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let Ok(modified) = fs::metadata(MOVEMENT_CONFIG_PATH).and_then(|meta| meta.modified()) else {
+        return;
+    };
+
+    //the first check only remembers the time, the file was already read when the plugin was built
+    if last_modified.replace(modified).is_none_or(|previous| previous == modified) {
+        return;
+    }
+
+    match MovementConfig::load(MOVEMENT_CONFIG_PATH) {
+        Ok(new_config) => {
+            info!("Reloaded movement config: {:?}", new_config);
+            *config = new_config;
+        }
+        Err(err) => warn!("Could not reload {}: {}", MOVEMENT_CONFIG_PATH, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagonals_are_no_faster_than_straight_lines() {
+        let config = MovementConfig { vertical_scale: 1.0, ..default() };
+
+        let straight = config.target_velocity(Vec2::new(1.0, 0.0), false, false);
+        let diagonal = config.target_velocity(Vec2::new(1.0, 1.0), false, false);
+
+        assert!((straight.length() - diagonal.length()).abs() < 0.001);
+    }
+
+    #[test]
+    fn sprint_and_sneak_scale_the_top_speed() {
+        let config = MovementConfig::default();
+        let right = Vec2::new(1.0, 0.0);
+
+        assert_eq!(config.target_velocity(right, true, false).x, config.max_speed * config.sprint_multiplier);
+        assert_eq!(config.target_velocity(right, false, true).x, config.max_speed * config.sneak_multiplier);
+    }
+
+    #[test]
+    fn speeds_up_and_slows_down_over_time() {
+        let config = MovementConfig { acceleration: 100.0, deceleration: 200.0, ..default() };
+        let target = Vec2::new(150.0, 0.0);
+
+        let velocity = config.step_velocity(Vec2::ZERO, target, 0.5);
+        assert_eq!(velocity, Vec2::new(50.0, 0.0));
+
+        let velocity = config.step_velocity(velocity, Vec2::ZERO, 0.1);
+        assert_eq!(velocity, Vec2::new(30.0, 0.0));
+
+        //never overshoots
+        assert_eq!(config.step_velocity(velocity, Vec2::ZERO, 10.0), Vec2::ZERO);
+    }
+}
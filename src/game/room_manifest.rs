@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use log::warn;
+use serde::Deserialize;
+
+use super::interaction::INTERACTABLES_FILE;
+use super::script::ActionCommand;
+
+///Name of the manifest file that can sit in each room folder
+pub const ROOM_MANIFEST: &str = "room.json";
+
+///Describes a room folder, either read from its `room.json` or guessed from the old naming convention
+/// all file names are relative to the room folder
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RoomManifest {
+    pub id: String,
+
+    //position of the room's bottom left corner on the level grid, in tiles
+    pub origin: [f32; 2],
+
+    pub layers: RoomLayers,
+    pub colliders: String,
+
+    //a room without this file simply has no interactables
+    #[serde(default = "default_interactables")]
+    pub interactables: String,
+
+    //actions run every time the player walks in, like `if not flag seen_intro then cutscene intro`
+    #[serde(default)]
+    pub on_enter: Vec<ActionCommand>,
+
+    //free-form extras (names, music, etc.) that the loader does not interpret
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct RoomLayers {
+    pub back: String,
+    pub deco: String,
+    pub fore: String,
+}
+
+fn default_interactables() -> String {
+    INTERACTABLES_FILE.to_string()
+}
+
+///Reads the manifest for a room folder
+/// falls back to the directory-name convention (`_x_y` folders, `*back*`/`*deco*`/`*fore*`/`*cldr*` files)
+/// when there is no `room.json` or it could not be read
+pub fn read_room_manifest(directory: &Path) -> Result<RoomManifest, Box<dyn Error>> {
+    let manifest_path = directory.join(ROOM_MANIFEST);
+
+    if manifest_path.is_file() {
+        match load_room_manifest(&manifest_path) {
+            Ok(manifest) => return Ok(manifest),
+            Err(err) => {
+                warn!("Could not read {}, using folder naming instead: {}", manifest_path.display(), err);
+            }
+        }
+    }
+
+    legacy_room_manifest(directory)
+}
+
+fn load_room_manifest(path: &Path) -> Result<RoomManifest, Box<dyn Error>> {
+    let file_content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&file_content)?)
+}
+
+///Builds a manifest out of the old conventions
+/// only the folder's own name is looked at, so underscores higher up the path do not matter
+fn legacy_room_manifest(directory: &Path) -> Result<RoomManifest, Box<dyn Error>> {
+    let folder_name = directory
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("room folder has no usable name: {}", directory.display()))?;
+
+    //folders are named _x_y
+    let location_info: Vec<&str> = folder_name.split('_').collect();
+    let (x, y) = match location_info.as_slice() {
+        [_, x, y] => (x.parse::<f32>()?, y.parse::<f32>()?),
+        _ => return Err(format!("room folder is not named _x_y: {}", directory.display()).into()),
+    };
+
+    let mut manifest = RoomManifest {
+        id: folder_name.to_string(),
+        origin: [x, y],
+        layers: RoomLayers::default(),
+        colliders: String::new(),
+        interactables: default_interactables(),
+        on_enter: Vec::new(),
+        metadata: HashMap::new(),
+    };
+
+    for item in fs::read_dir(directory)? {
+        let item = item?;
+        let Some(file_name) = item.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+
+        if file_name.contains("back") {
+            manifest.layers.back = file_name;
+        } else if file_name.contains("fore") {
+            manifest.layers.fore = file_name;
+        } else if file_name.contains("deco") {
+            manifest.layers.deco = file_name;
+        } else if file_name.contains("cldr") {
+            manifest.colliders = file_name;
+        }
+    }
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    //a room folder in the temp directory holding `files`, each test gets its own so they can run side by side
+    fn room_folder(test: &str, folder_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir()
+            .join(format!("harken_room_manifest_{}_{}", test, std::process::id()))
+            .join(folder_name);
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (name, content) in files {
+            fs::write(directory.join(name), content).unwrap();
+        }
+        directory
+    }
+
+    fn remove(directory: &Path) {
+        fs::remove_dir_all(directory.parent().unwrap()).unwrap();
+    }
+
+    const LEGACY_FILES: [(&str, &str); 4] = [
+        ("hall_back.png", ""),
+        ("hall_deco.png", ""),
+        ("hall_fore.png", ""),
+        ("hall_cldr.svg", ""),
+    ];
+
+    #[test]
+    fn reads_a_full_manifest() {
+        let directory = room_folder("full", "kitchen", &[(ROOM_MANIFEST, r#"{
+            "id": "kitchen",
+            "origin": [4, -2.5],
+            "layers": { "back": "k_back.png", "deco": "k_deco.png", "fore": "k_fore.png" },
+            "colliders": "k_cldr.svg",
+            "interactables": "k_interactables.json",
+            "on_enter": ["set_flag been_in_kitchen"],
+            "metadata": { "name": "Kitchen", "music": "kettle.ogg" }
+        }"#)]);
+
+        let manifest = read_room_manifest(&directory).unwrap();
+        remove(&directory);
+
+        assert_eq!(manifest.id, "kitchen");
+        assert_eq!(manifest.origin, [4.0, -2.5]);
+        assert_eq!(manifest.layers, RoomLayers {
+            back: "k_back.png".to_string(),
+            deco: "k_deco.png".to_string(),
+            fore: "k_fore.png".to_string(),
+        });
+        assert_eq!(manifest.colliders, "k_cldr.svg");
+        assert_eq!(manifest.interactables, "k_interactables.json");
+        assert_eq!(manifest.on_enter, vec![ActionCommand::SetFlag("been_in_kitchen".to_string())]);
+        assert_eq!(manifest.metadata["music"], "kettle.ogg");
+    }
+
+    #[test]
+    fn optional_fields_have_defaults() {
+        let directory = room_folder("defaults", "attic", &[(ROOM_MANIFEST, r#"{
+            "id": "attic",
+            "origin": [0, 9],
+            "layers": { "back": "a_back.png", "deco": "a_deco.png", "fore": "a_fore.png" },
+            "colliders": "a_cldr.svg"
+        }"#)]);
+
+        let manifest = read_room_manifest(&directory).unwrap();
+        remove(&directory);
+
+        assert_eq!(manifest.interactables, INTERACTABLES_FILE);
+        assert!(manifest.on_enter.is_empty());
+        assert!(manifest.metadata.is_empty());
+    }
+
+    #[test]
+    fn bad_manifests_fall_back_to_folder_naming() {
+        let mut files = LEGACY_FILES.to_vec();
+        files.push((ROOM_MANIFEST, r#"{ "id": "_3_7", "origin": "not a list" }"#));
+        let directory = room_folder("fallback", "_3_7", &files);
+
+        let manifest = read_room_manifest(&directory).unwrap();
+        remove(&directory);
+
+        assert_eq!(manifest.id, "_3_7");
+        assert_eq!(manifest.origin, [3.0, 7.0]);
+        assert_eq!(manifest.layers.back, "hall_back.png");
+        assert_eq!(manifest.layers.deco, "hall_deco.png");
+        assert_eq!(manifest.layers.fore, "hall_fore.png");
+        assert_eq!(manifest.colliders, "hall_cldr.svg");
+        assert_eq!(manifest.interactables, INTERACTABLES_FILE);
+    }
+
+    #[test]
+    fn legacy_folders_need_an_x_y_name() {
+        for (test, folder_name) in [("unnamed", "hallway"), ("not_numbers", "_a_b"), ("too_many", "_1_2_3")] {
+            let directory = room_folder(test, folder_name, &LEGACY_FILES);
+            let result = read_room_manifest(&directory);
+            remove(&directory);
+
+            assert!(result.is_err(), "{folder_name} should not be a room");
+        }
+    }
+}
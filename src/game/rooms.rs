@@ -1,36 +1,73 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
-use bevy::{a11y::accesskit::Rect, transform::commands};
+use bevy::a11y::accesskit::Rect;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
 use log::warn;
 
-use crate::{game::{read_lines, ColliderType}, IS_IN_WINDOWS, PIXEL_SCALE};
+use crate::{game::ColliderType, PIXEL_SCALE};
 
-use super::{Collider, DebugMode, GameState, Player, Shadow, };
+use super::interaction::{self, Interactable, InteractableMemory};
+use super::interactable_sprite;
+use super::room_manifest::read_room_manifest;
+use super::script::{ActionCommand, InteractableScript, ScriptContext};
+use super::broadphase::ColliderGrid;
+use super::collider_merge::merge_collider_rects;
+use super::svg::{self, ColliderSvg, SvgRect};
+use super::transition::{Destination, PendingSpawn};
+use super::world_state::WorldState;
+use super::{Collider, GameState, Player, Shadow, };
 
 use crate::resources::*;
 
 
 pub fn room_plugin(app: &mut App){
     app
+        .add_systems(OnEnter(GameState::LevelLoading), unload_level_rooms.before(load_level_room_data))
 
         .add_systems(OnEnter(GameState::Loading), (
+            place_player_at_spawn,
             spawn_colliders,
+            spawn_room_interactables,
             display_rooms,
         ).chain().run_if(in_state(GameState::Loading)))
 
+        .init_resource::<PendingRoomEntry>()
+        .add_event::<ColliderEdit>()
         .add_systems(Update, (
             room_status,
+            run_room_entry_actions,
+            apply_collider_edits,
             despawn_rooms,
         ).chain().run_if(in_state(GameState::Running)));
 }
 
+///Replaces a room's colliders with the ones in `collider_svg`, sent by the collider editor as tiles are painted
+#[derive(Event, Debug, Clone)]
+pub struct ColliderEdit {
+    pub room: String,
+    pub collider_svg: ColliderSvg,
+}
+
+///Rooms the player walked into that were not active, waiting to run their `on_enter` actions
+/// a room that has to be loaded first sends the game through GameState::Loading, so they wait until it is Running again
+#[derive(Resource, Debug, Default)]
+pub struct PendingRoomEntry(Vec<String>);
+
 
 #[derive(Component, Clone, Debug)]
-struct Room {
-    identifier: String,
+pub struct Room {
+    pub identifier: String,
+
+    //empty when the room has no collider file
+    pub collider_path: PathBuf,
+
+    //where its interactables are read from and saved to, the file might not exist yet
+    pub interactables_path: PathBuf,
 
     location: Transform,
     area: Rect,
@@ -40,14 +77,60 @@ struct Room {
     foreground_path: String,
     
     colliders: Vec<Collider>,
+    interactables: Vec<(Interactable, InteractableScript)>,
+    on_enter: Vec<ActionCommand>,
+    metadata: HashMap<String, serde_json::Value>,
 
     active: bool,
     lifetime: u32,
 
 }
 
+impl Room {
+    ///Where the player's feet go to stand on a tile of the room's collider file (x right, y down)
+    pub fn tile_position(&self, x: f32, y: f32) -> Vec2 {
+        let top_left = tile_top_left(&self.location, &self.area, x, y);
+        Vec2::new(top_left.x, top_left.y - PIXEL_SCALE)
+    }
+
+    ///The tile of the room's collider file under a point in the world, None outside the room
+    pub fn tile_at(&self, point: Vec2) -> Option<(i32, i32)> {
+        let x = ((point.x - self.location.translation.x) / PIXEL_SCALE).floor();
+        //the inverse of tile_top_left, rows count down from the top of the room
+        let y = ((self.area.height() as f32 - PIXEL_SCALE + self.location.translation.y - point.y) / PIXEL_SCALE).ceil() - 1.0;
+
+        let (width, height) = (self.area.width() as f32 / PIXEL_SCALE, self.area.height() as f32 / PIXEL_SCALE);
+        (x >= 0.0 && y >= 0.0 && x < width && y < height).then_some((x as i32, y as i32))
+    }
+
+    ///The part of the world the room covers
+    pub fn area(&self) -> Rect {
+        self.area
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    ///The interactables the room spawns when it becomes active, as they were loaded or last saved
+    pub fn saved_interactables(&self) -> impl Iterator<Item = &Interactable> {
+        self.interactables.iter().map(|(interactable, _)| interactable)
+    }
+
+    ///Swaps in the interactables that were just saved, so they are what the room spawns from now on
+    pub fn set_saved_interactables(&mut self, interactables: Vec<Interactable>) {
+        self.interactables = interaction::with_scripts(self.interactables_path.display(), interactables);
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let (x, y) = (point.x as f64, point.y as f64);
+        self.area.x0 <= x && x < self.area.x1 && self.area.y0 <= y && y < self.area.y1
+    }
+}
+
+///Marks everything that belongs to a room, it is despawned along with the room
 #[derive(Component)]
-struct RoomId(String);
+pub struct RoomId(pub String);
 
 
 fn display_rooms(
@@ -62,7 +145,7 @@ fn display_rooms(
         
         if room.active {
             //this is an active room that should be displayed
-            println!("Attempting to display room: {:?}", room.backdrop_path);
+            debug!("Attempting to display room: {} {:?} {:?}", room.identifier, room.backdrop_path, room.metadata);
             let backdrop = asset_server.load(room.backdrop_path.clone());
             let decoration = asset_server.load(room.decoration_path.clone());
             let foreground = asset_server.load(room.foreground_path.clone());
@@ -154,6 +237,7 @@ fn room_status(
     mut rooms: Query<&mut Room>,
     players: Query<(&Transform, &Player), Without<Shadow>>,
     mut game_state: ResMut<NextState<GameState>>,
+    mut entered: ResMut<PendingRoomEntry>,
 ) {
     'rooms: for mut room in &mut rooms {
         //set room to active it the room's rect intersects with the player's rect
@@ -178,6 +262,7 @@ fn room_status(
 
                 } else {
                     //player is intersecting an inactive room, we now need to re-load rooms and display
+                    entered.0.push(room.identifier.clone());
                     if room.lifetime == 0{
                         room.active = true;
                         needs_reload = true;
@@ -208,54 +293,164 @@ fn room_status(
 /// This function is scheduled by bevy and will run in the loading state
 fn spawn_colliders(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-
     rooms: Query<&Room>,
-    in_debug: Res<DebugMode>,
+    mut collider_grid: ResMut<ColliderGrid>,
 ) {
 
 
     for room in &rooms {
         if room.active {
             for collider in &room.colliders {
-                commands.spawn((
-                    *collider,
+                let entity = commands.spawn((
+                    collider.clone(),
                     RoomId(room.identifier.clone())
-                ));
-    
-                if in_debug.0 {
-                    // info!("A collider was created at: {:?}", collider.transform);
+                )).id();
+                collider_grid.insert(entity, collider.rect());
+            }
+        }
+    }
+    info!("Broadphase is tracking {} colliders", collider_grid.len());
+}
+
+///Rebuilds the colliders of edited rooms, the collision the player feels follows the editor straight away
+fn apply_collider_edits(
+    mut commands: Commands,
+    mut edits: EventReader<ColliderEdit>,
+    mut rooms: Query<&mut Room>,
+    colliders: Query<(Entity, &RoomId), With<Collider>>,
+    mut collider_grid: ResMut<ColliderGrid>,
+) {
+    for edit in edits.read() {
+        let Some(mut room) = rooms.iter_mut().find(|room| room.identifier == edit.room) else {
+            continue;
+        };
 
-                    let tex;
+        let (new_colliders, _) = load_colliders(&edit.collider_svg, &room.location, &room.area);
+        room.colliders = new_colliders;
 
-                    if IS_IN_WINDOWS{
-                        tex = asset_server.load("textures\\Rooms\\cldr.png");
-                    }else{
-                        tex = asset_server.load("textures/Rooms/cldr.png");
-                    }
+        if !room.active {
+            continue;
+        }
 
-                    commands.spawn((
-                        SpriteBundle {
-                            sprite: Sprite {
-                                custom_size: Some(Vec2::new(1.0, 1.0)),
-                                anchor: Anchor::TopLeft,
-                                ..default()
-                            },
-                            transform: collider.transform,
-                            texture: tex,
-                            ..default()
-                        },
-                        RoomId(room.identifier.clone())
-                    ));
-                }
+        for (entity, room_id) in &colliders {
+            if room_id.0 == room.identifier {
+                commands.entity(entity).despawn_recursive();
+                collider_grid.remove(entity);
             }
         }
+        for collider in &room.colliders {
+            let entity = commands.spawn((collider.clone(), RoomId(room.identifier.clone()))).id();
+            collider_grid.insert(entity, collider.rect());
+        }
     }
 }
 
+///Runs the `on_enter` actions of rooms the player just walked into, once they are displayed
+fn run_room_entry_actions(
+    mut entered: ResMut<PendingRoomEntry>,
+    game_state: Res<NextState<GameState>>,
+    rooms: Query<&Room>,
+    mut context: ScriptContext,
+) {
+    //a cutscene started now would begin in the middle of loading the room
+    if entered.0.is_empty() || matches!(*game_state, NextState::Pending(_)) {
+        return;
+    }
+
+    for identifier in std::mem::take(&mut entered.0) {
+        let Some(room) = rooms.iter().find(|room| room.identifier == identifier) else {
+            continue;
+        };
+        for command in &room.on_enter {
+            context.run(command);
+        }
+    }
+}
+
+///Spawns the interactables of every active room that does not have them out yet, with their sprites
+/// anything the player already did to them is put back from the InteractableMemory
+fn spawn_room_interactables(
+    mut commands: Commands,
+    rooms: Query<&Room>,
+    spawned: Query<&RoomId, With<Interactable>>,
+    (memory, current_level): (Res<InteractableMemory>, Res<CurrentLevel>),
+    world_state: Res<WorldState>,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    //rooms that were already active before this load keep what they have
+    let already_spawned: HashSet<&str> = spawned.iter().map(|room_id| room_id.0.as_str()).collect();
+
+    for room in rooms.iter().filter(|room| room.active) {
+        if already_spawned.contains(room.identifier.as_str()) {
+            continue;
+        }
+
+        for (interactable, script) in &room.interactables {
+            let mut interactable = interactable.clone();
+            memory.restore(current_level.0, &mut interactable);
+            let mut entity = commands.spawn((script.clone(), RoomId(room.identifier.clone())));
+            interactable_sprite::insert_sprite(&mut entity, &interactable, &world_state, &asset_server, &mut layouts);
+            entity.insert(interactable);
+        }
+    }
+}
+
+///Moves the player to where a room transition said they should arrive, once the new level's rooms exist
+fn place_player_at_spawn(
+    mut commands: Commands,
+    pending_spawn: Option<Res<PendingSpawn>>,
+    rooms: Query<&Room>,
+    mut players: Query<&mut Transform, (With<Player>, Without<Shadow>)>,
+    mut shadows: Query<&mut Transform, With<Shadow>>,
+) {
+    let Some(pending_spawn) = pending_spawn else {
+        return;
+    };
+    commands.remove_resource::<PendingSpawn>();
+
+    let destination = &pending_spawn.0;
+    let Some(room) = rooms.iter().find(|room| room.identifier == destination.room) else {
+        error!("Could not find room {} to place the player in", destination.room);
+        return;
+    };
+
+    //the spawn tile is where the player's feet go, so stand on the bottom of it
+    let feet = room.tile_position(destination.spawn.x, destination.spawn.y);
+    info!("Placing player in room {} at {:?}", room.identifier, feet);
+
+    for mut transform in &mut players {
+        transform.translation.x = feet.x;
+        transform.translation.y = feet.y;
+    }
+    for mut transform in &mut shadows {
+        transform.translation.x = feet.x - (PIXEL_SCALE * 0.125);
+        transform.translation.y = feet.y;
+    }
+}
+
+///Removes every room of the current level and everything that was spawned for them
+/// This function is scheduled by bevy and will run when a new level starts loading
+fn unload_level_rooms(
+    mut commands: Commands,
+    rooms: Query<Entity, With<Room>>,
+    room_objects: Query<Entity, With<RoomId>>,
+    mut collider_grid: ResMut<ColliderGrid>,
+    mut entered: ResMut<PendingRoomEntry>,
+) {
+    for entity in rooms.iter().chain(room_objects.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    collider_grid.clear();
+
+    //the next level can have rooms with the same names
+    entered.0.clear();
+}
+
 fn despawn_rooms(
     mut rooms: Query<&mut Room>,
     mut room_objects: Query<(Entity, &RoomId)>,
+    mut collider_grid: ResMut<ColliderGrid>,
     mut commands: Commands,
 ) {
     //despawn any room that is no longer active
@@ -271,6 +466,7 @@ fn despawn_rooms(
                     if room_object.1.0 == room.identifier {
                         //despawn this entity and all of its components
                         commands.entity(room_object.0).despawn_recursive();
+                        collider_grid.remove(room_object.0);
                     }
                 }
             }
@@ -278,7 +474,7 @@ fn despawn_rooms(
     }
 }
 
-fn read_directory(path: &String) -> Result<fs::ReadDir, io::Error> {
+fn read_directory(path: &str) -> Result<fs::ReadDir, io::Error> {
     let paths = fs::read_dir(path);
 
     match paths {
@@ -296,8 +492,6 @@ fn read_directory(path: &String) -> Result<fs::ReadDir, io::Error> {
 /// This function is scheduled by bevy and will run in the loadinglevel state
 pub fn load_level_room_data(
     mut commands: Commands,
-    in_debug: Res<DebugMode>,
-    asset_server: Res<AssetServer>,
     current_level: Res<CurrentLevel>,
 
     mut game_state: ResMut<NextState<GameState>>,
@@ -308,6 +502,8 @@ pub fn load_level_room_data(
 
     let paths = read_directory(&rooms_path);
     
+    //interactables can depend on ones in other rooms, so they are checked once the whole level is read
+    let mut level_interactables = Vec::<Interactable>::new();
 
     for item in paths.unwrap() {
 
@@ -318,38 +514,20 @@ pub fn load_level_room_data(
                     Ok(file_type) => {
                         if file_type.is_dir() {
                             // info!("Found directory in rooms folder: {}", item.path().display());
-                            let new_room = create_room(item.path().display().to_string());
+                            let new_room = match create_room(&item.path()) {
+                                Ok(room) => room,
+                                Err(err) => {
+                                    error!("Could not create room from {}: {}", item.path().display(), err);
+                                    continue;
+                                }
+                            };
                             info!("Creating room with data: {:?}", &new_room);
+                            level_interactables.extend(new_room.interactables.iter().map(|(interactable, _)| interactable.clone()));
 
                             commands.spawn(
                                 new_room.clone()
                             );
 
-                            if in_debug.0 {//this needs to be moved! Once a room despawns there is no way for it to return
-                                println!("SPAWNING ROOM BORDER");
-                                let tex: Handle<Image> = if IS_IN_WINDOWS{
-                                    asset_server.load("textures\\rooms\\room_border.png")
-                                }else{
-                                    asset_server.load("textures/rooms/room_border.png")
-                                };
-                                commands.spawn((
-                                    SpriteBundle {
-                                        sprite: Sprite {
-                                            custom_size: Some(Vec2::new(1.0, 1.0)),
-                                            anchor: Anchor::BottomLeft,
-                                            ..default()
-                                        },
-                                        transform: Transform {
-                                            translation: new_room.location.translation.clone().with_z(50.0),
-                                            scale: Vec3::new(new_room.area.width() as f32, new_room.area.height() as f32, 0.0),
-                                            ..default()
-                                        },
-                                        texture: tex,
-                                        ..default()
-                                    },
-                                    RoomId(item.path().display().to_string())
-                                ));
-                            }
                             info!("Room created at location: {:?}", new_room.location.translation);
 
 
@@ -368,238 +546,305 @@ pub fn load_level_room_data(
                 warn!("Could not read item in rooms folder");
             }
         }
+    }
 
-    game_state.set(GameState::Loading);
-}}
+    for err in interaction::validate_dependancies(&level_interactables) {
+        error!("Bad dependancies in {}, {}", rooms_path, err);
+    }
 
-///creates a new room based on the path of a room directory, as long as the folder structure is correct this should be the only input this needs
-fn create_room(directory_path: String) -> Room {
-    // info!("Creating Room from directory: {}", directory_path);
+    game_state.set(GameState::Loading);
+}
 
-    let location_info: Vec<&str> = directory_path.split("_").collect();
-    // info!("Attempting to identify location in path: {:?}", location_info);
+///creates a new room based on the path of a room directory
+/// the room is described by its `room.json` manifest, or by the folder naming convention if it has none
+pub(super) fn create_room(directory_path: &Path) -> Result<Room, Box<dyn Error>> {
+    let manifest = read_room_manifest(directory_path)?;
+    info!("Creating room {} from manifest: {:?}", manifest.id, manifest);
 
     let location = Transform {
         translation: Vec3::new(
-            location_info[1].parse::<f32>().unwrap() * PIXEL_SCALE,
-            location_info[2].parse::<f32>().unwrap() * PIXEL_SCALE,
+            manifest.origin[0] * PIXEL_SCALE,
+            manifest.origin[1] * PIXEL_SCALE,
             -1.0,
         ),
         scale: Vec3::new(6.0, 6.0, 0.0),
         ..default()
     };
 
-
     info!("Creating room at location:{:?}", location.translation);
 
     let mut room = Room {
-        identifier: directory_path.clone(),
-        location: location,
+        identifier: manifest.id.clone(),
+        collider_path: PathBuf::new(),
+        interactables_path: directory_path.join(&manifest.interactables),
+        location,
         area: Rect{..default()},
         
-        backdrop_path: "".to_string(),
-        decoration_path: "".to_string(),
-        foreground_path: "".to_string(),
+        backdrop_path: asset_path(directory_path, &manifest.layers.back),
+        decoration_path: asset_path(directory_path, &manifest.layers.deco),
+        foreground_path: asset_path(directory_path, &manifest.layers.fore),
         colliders: Vec::<Collider>::new(),
+        interactables: Vec::new(),
+        on_enter: manifest.on_enter,
+        metadata: manifest.metadata,
 
         active: false,
         lifetime: 0,
     };
 
-    let room_items = read_directory(&directory_path).unwrap();
+    if room.interactables_path.is_file() {
+        match interaction::load_interactables(&room.interactables_path) {
+            Ok(interactables) => room.interactables = interactables,
+            Err(err) => error!("Could not load interactables for room {}: {}", room.identifier, err),
+        }
+    }
 
-    for item in room_items {
-        match item {
-            Ok(item) => {
-                let mut item_name = item.path().display().to_string();
-                item_name = item_name.replace("assets/", "");
-
-                warn!("Found item: {} in room folder: {}", item_name, &directory_path);
-
-                if item_name.contains("back") {
-                    room.backdrop_path = item_name.clone();
-                } else if item_name.contains("fore") {
-                    room.foreground_path = item_name.clone();
-                } else if item_name.contains("deco") {
-                    room.decoration_path = item_name.clone();
-                } else if item_name.contains("cldr") {
-                    room.area = get_area(&item_name, &room.location);
-
-                    let colliders = load_colliders(item_name.clone(), &location, &room.area);
-                    room.colliders = colliders.clone();
-                }
-            }
-            Err(_) => {
-                warn!("Could not read item in room folder");
-            }
+    if manifest.colliders.is_empty() {
+        warn!("Room {} has no collider file", room.identifier);
+        return Ok(room);
+    }
+
+    room.collider_path = directory_path.join(&manifest.colliders);
+    match svg::load_collider_svg(&room.collider_path) {
+        Ok(collider_svg) => {
+            room.area = get_area(&collider_svg, &room.location);
+            let (colliders, removed) = load_colliders(&collider_svg, &location, &room.area);
+            info!(
+                "Room {} loaded {} colliders ({} removed by merging {} rects)",
+                room.identifier, colliders.len(), removed, collider_svg.rects.len()
+            );
+            room.colliders = colliders;
+        }
+        Err(err) => {
+            error!("Could not load colliders for room {}: {}", room.identifier, err);
         }
     }
-    return room;
+    Ok(room)
+}
+
+///Turns a file in a room folder into a path the asset server understands (relative to `assets/`)
+fn asset_path(directory_path: &Path, file_name: &str) -> String {
+    let full_path = directory_path.join(file_name);
+    full_path
+        .strip_prefix("assets")
+        .unwrap_or(&full_path)
+        .display()
+        .to_string()
 }
 
-///This function will parse the collider file and return a vector of colliders
+///This function will turn the rects of a parsed collider file into a vector of colliders
+/// touching tiles of the same ColliderType are merged first, the number of colliders that saved is returned alongside
 /// This function is NOT scheduled by bevy
-fn load_colliders(backdrop_path: String, room_location: &Transform, room_area: &Rect) -> Vec<Collider> {
+fn load_colliders(collider_svg: &ColliderSvg, room_location: &Transform, room_area: &Rect) -> (Vec<Collider>, usize) {
+    let typed_rects: Vec<(ColliderType, SvgRect)> = collider_svg
+        .rects
+        .iter()
+        .map(|rect| (collider_type_for(&rect.fill), rect.clone()))
+        .collect();
 
-    let mut collider_path: String = backdrop_path.replace("back", "cldr");
-    collider_path = collider_path.replace(".png", ".svg");
+    let (merged_rects, removed) = merge_collider_rects(&typed_rects);
 
+    let mut colliders = Vec::<Collider>::new();
 
-    info!("parsing level colliders from: {}", collider_path);
+    for (st, rect) in merged_rects {
+        let mut destination = None;
 
-    let mut colliders = Vec::<Collider>::new();
-    let mut i = 0;
+        if st == ColliderType::ChangeRoom {
+            match destination_for(&rect) {
+                Ok(found) => destination = found,
+                Err(err) => error!("Bad exit at tile {},{}: {}", rect.x, rect.y, err),
+            }
+            if destination.is_none() {
+                warn!("Exit at tile {},{} has nowhere to go and will do nothing", rect.x, rect.y);
+            }
+        }
 
-    if let Ok(lines) = read_lines(collider_path) {
-        // Consumes the iterator, returns an (Optional) String
-        for line in lines.flatten() {
-            //seems like for each line of this file we can create a new collider object
-            //lets parse out the important info first
+        let top_left = tile_top_left(room_location, room_area, rect.x, rect.y);
 
-            //line zero is not useful to us so we will increment i at the beginning of the loop to skip index 0
-            i += 1;
+        colliders.push(Collider {
+            transform: Transform {
+                translation: top_left.extend(50.0),
+                scale: Vec3::new(rect.width * PIXEL_SCALE, rect.height * PIXEL_SCALE, 0.0),
+                ..default()
+            },
+            style: st,
+            destination,
+        });
+    }
+    (colliders, removed)
+}
 
-            //the first 2 lines are not useful
-            if i <= 2 {
-                continue;
-            }
+///Reads where a ChangeRoom rect leads, e.g. `data-level="1" data-room="_8_9" data-spawn="3 4"`
+/// returns None when the rect has no destination at all
+fn destination_for(rect: &SvgRect) -> Result<Option<Destination>, String> {
+    if !["level", "room", "spawn"].iter().any(|key| rect.data.contains_key(*key)) {
+        return Ok(None);
+    }
 
-            //stop this loop if we find the SVG end tag
-            if line.contains("/svg") {
-                // println!("End of file: {}", line);
-                break;
-            }
+    let get = |key: &str| rect.data.get(key).ok_or(format!("missing data-{key}"));
+
+    let level = get("level")?
+        .parse::<u32>()
+        .map_err(|_| format!("data-level is not a level number: {:?}", rect.data["level"]))?;
+    let room = get("room")?.clone();
+
+    let spawn: Vec<f32> = get("spawn")?
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("data-spawn is not two numbers: {:?}", rect.data["spawn"]))?;
+    let [x, y] = spawn[..] else {
+        return Err(format!("data-spawn is not two numbers: {:?}", rect.data["spawn"]));
+    };
 
-            //create important variables we will use to create our collider
-            let mut x: i16 = 0;
-            let mut y: i16 = 0;
-            let mut w: i16 = 0;
-            let mut h: i16 = 0;
-            let mut col: &str = "";
+    Ok(Some(Destination { level, room, spawn: Vec2::new(x, y) }))
+}
 
-            let pretty_line = line.trim();
-            let parts = pretty_line.split("\"").collect::<Vec<_>>();
+///The `data-*` attributes destination_for reads back, for exits painted in the collider editor
+pub fn exit_data(destination: &Destination) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("level".to_string(), destination.level.to_string()),
+        ("room".to_string(), destination.room.clone()),
+        ("spawn".to_string(), format!("{} {}", destination.spawn.x, destination.spawn.y)),
+    ])
+}
 
-            let mut ints = Vec::<i16>::new();
+///World position of the top left corner of a tile in a room's collider file, this is where colliders are anchored
+fn tile_top_left(room_location: &Transform, room_area: &Rect, x: f32, y: f32) -> Vec2 {
+    Vec2::new(
+        room_location.translation.x + x * PIXEL_SCALE,
+        (room_area.height() as f32 - PIXEL_SCALE) + room_location.translation.y - (y * PIXEL_SCALE),
+    )
+}
 
-            let mut count = 0;
-            for part in parts {
-                count += 1;
-                if (count - 1) % 2 == 0 {
-                    continue;
-                }
+///THIS ACTS AS A KEY TO WHICH COLORS YOU SHOULD BE MAKING YOUR COLLIDERS TO GET THE DESIRED COLLIDERTYPE
+pub fn collider_type_for(fill: &str) -> ColliderType {
+    match fill {
+        "#000000" => ColliderType::RIGID,
 
-                match part.parse::<i32>() {
-                    Ok(_) => {
-                        // println!("Parsed: {}", part.parse::<i16>().unwrap());
-                        ints.push(part.parse::<i16>().unwrap());
-                    }
+        "#00FF00" => ColliderType::ChangeRoom,
 
-                    Err(_) => {
-                        if part.contains("#") {
-                            // println!("Found Color: {}", part);
-                            col = part;
-                        } else {
-                            warn!("Could not parse int from part: {}", part);
-                        }
-                    }
-                }
-            }
+        _ => ColliderType::Interactable,
+    }
+}
 
-            count = 0;
-            for item in ints {
-                match count % 4 {
-                    0 => x = item,
+///The color to paint a collider of this type with, the other way around from collider_type_for
+pub fn collider_fill(style: ColliderType) -> &'static str {
+    match style {
+        ColliderType::RIGID => "#000000",
+        ColliderType::ChangeRoom => "#00FF00",
+        ColliderType::Interactable => "#0000FF",
+    }
+}
 
-                    1 => y = item,
 
-                    2 => w = item,
+///This function will return the area of the room described by a parsed collider file
+/// The svg's size (in tiles) is the size of the room
+/// This function is NOT scheduled by bevy
+fn get_area(collider_svg: &ColliderSvg, room_location: &Transform) -> Rect {
+    let area = Rect::new(
+        room_location.translation.x as f64, 
+        room_location.translation.y as f64, 
+        room_location.translation.x as f64 + (collider_svg.width * PIXEL_SCALE) as f64,
+        room_location.translation.y as f64 + (collider_svg.height * PIXEL_SCALE) as f64
+    );
+    info!("Creating Room Area : {:?}", area);
+    area
+}
 
-                    3 => h = item,
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    _ => {
-                        error!("This should never print")
-                    }
-                }
-                count += 1;
-            }
+    const HALL: &str = "assets/textures/rooms/L1/_15_2";
 
-            //THIS ACTS AS A KEY TO WHICH COLORS YOU SHOULD BE MAKING YOUR COLLIDERS TO GET THE DESIRED COLLIDERTYPE
-            let st = match col {
-                "#000000" => ColliderType::RIGID,
+    fn exit(data: &[(&str, &str)]) -> SvgRect {
+        SvgRect {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+            fill: "#00FF00".to_string(),
+            data: data.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+        }
+    }
 
-                "#00FF00" => ColliderType::ChangeRoom,
+    #[test]
+    fn exits_read_their_destination() {
+        assert_eq!(
+            destination_for(&exit(&[("level", "2"), ("room", "_8_9"), ("spawn", "3, 4")])),
+            Ok(Some(Destination { level: 2, room: "_8_9".to_string(), spawn: Vec2::new(3.0, 4.0) }))
+        );
 
-                _ => ColliderType::Interactable,
-            };
+        //an exit without any data just goes nowhere, other data does not make it one
+        assert_eq!(destination_for(&exit(&[])), Ok(None));
+        assert_eq!(destination_for(&SvgRect { data: BTreeMap::from([("note".to_string(), "door".to_string())]), ..exit(&[]) }), Ok(None));
+    }
 
-            col = col.split("#").collect::<Vec<_>>()[1];
-            // println!("COLOR FOUND: {}", col);
+    #[test]
+    fn painted_exits_read_back_where_they_lead() {
+        let destination = Destination { level: 3, room: "_0_0".to_string(), spawn: Vec2::new(2.0, 4.5) };
+        let painted = SvgRect { data: exit_data(&destination), ..exit(&[]) };
 
-            // println!("Creating Collider with x:{} y:{} w:{} h:{} of type:{:?}", ((x as f32*PIXEL_SCALE)  - SCREEN_WIDTH/2.0), ((SCREEN_HEIGHT / 2.0) + (y as f32*PIXEL_SCALE) as f32), w as f32*PIXEL_SCALE, h as f32*PIXEL_SCALE, st);
-            colliders.push(Collider {
-                // transform: Rect::new((x*96).into(), (y*96).into(), ((x+w)*96).into(), ((y+h)*96).into()),
+        assert_eq!(painted.data["spawn"], "2 4.5");
+        assert_eq!(destination_for(&painted), Ok(Some(destination)));
+    }
 
-                //(x as f32 * PIXEL_SCALE) + PIXEL_SCALE * 2.0 - (),
-                transform: Transform {
-                    translation: Vec3::new(
-                        room_location.translation.x + x as f32 * PIXEL_SCALE,
-                        (room_area.height() as f32 - (1.0 * PIXEL_SCALE)) as f32+ room_location.translation.y - (y as f32 * PIXEL_SCALE),
-                        50.0
-                    ),
-                    scale: Vec3::new(w as f32 * PIXEL_SCALE, h as f32 * PIXEL_SCALE, 0.0),
-                    ..default()
-                },
-                style: st,
-            });
+    #[test]
+    fn half_written_exits_are_errors() {
+        let error = |data: &[(&str, &str)]| destination_for(&exit(data)).unwrap_err();
+
+        assert_eq!(error(&[("room", "_8_9"), ("spawn", "3 4")]), "missing data-level");
+        assert_eq!(error(&[("level", "1"), ("spawn", "3 4")]), "missing data-room");
+        assert_eq!(error(&[("level", "1"), ("room", "_8_9")]), "missing data-spawn");
+
+        assert!(error(&[("level", "one"), ("room", "_8_9"), ("spawn", "3 4")]).starts_with("data-level is not a level number"));
+        assert!(error(&[("level", "-1"), ("room", "_8_9"), ("spawn", "3 4")]).starts_with("data-level is not a level number"));
+
+        for spawn in ["3", "3 4 5", "3 x", ""] {
+            assert!(error(&[("level", "1"), ("room", "_8_9"), ("spawn", spawn)]).starts_with("data-spawn is not two numbers"), "{spawn:?}");
         }
     }
-    return colliders;
-}
 
+    #[test]
+    fn tiles_count_down_from_the_top_of_the_room() {
+        //a 4 by 3 tile room with its bottom left corner at tile 2,1 of the level
+        let location = Transform::from_xyz(2.0 * PIXEL_SCALE, PIXEL_SCALE, 0.0);
+        let area = Rect::new(96.0, 48.0, 96.0 + 4.0 * 48.0, 48.0 + 3.0 * 48.0);
 
-///This function will parse the collider file and return the area of the room
-/// This area will be the smallest rectangle that can contain all colliders
-/// This function is NOT scheduled by bevy
-fn get_area(backdrop_path: &String, room_location: &Transform) -> Rect {
-    let mut collider_path: String = backdrop_path.replace("back", "cldr");
-    info!("Calculating room area using: {}", collider_path);
-    collider_path = collider_path.replace(".png", ".svg");
-    collider_path = collider_path.replace("textures", "assets/textures");
-
-    info!("AFTER: Calculating room area using: {}", collider_path);
+        //the top row's top left corner sits one tile below the top of the room, where colliders are anchored
+        assert_eq!(tile_top_left(&location, &area, 0.0, 0.0), Vec2::new(96.0, 144.0));
+        assert_eq!(tile_top_left(&location, &area, 3.0, 2.0), Vec2::new(240.0, 48.0));
+        assert_eq!(tile_top_left(&location, &area, 0.5, 1.0), Vec2::new(120.0, 96.0));
+    }
 
-    let mut i = 0;
+    #[test]
+    fn tile_positions_and_tiles_agree() {
+        let hall = create_room(Path::new(HALL)).unwrap();
+        assert_eq!(hall.area(), Rect::new(720.0, 96.0, 720.0 + 34.0 * 48.0, 96.0 + 8.0 * 48.0));
 
-    let mut height = 0.0;
-    let mut width = 0.0;
-    if let Ok(lines) = read_lines(&collider_path) {
-        // Consumes the iterator, returns an (Optional) String
-        for line in lines.flatten() {
-            println!("{:?} Line {}:{}", &collider_path, &i, line);
+        //feet stand on the bottom edge of the tile
+        assert_eq!(hall.tile_position(0.0, 0.0), Vec2::new(720.0, 384.0));
+        assert_eq!(hall.tile_position(33.0, 7.0), Vec2::new(2304.0, 48.0));
 
-            i += 1;
-            
-                //line 2 contains the SVG info for width and height which we will use to find the size of the room
-            if i == 2 {
-                let lineparts = line.split("\"").collect::<Vec<_>>();
-                width = lineparts[3].parse::<u32>().unwrap() as f32;
-                height = lineparts[5].parse::<u32>().unwrap() as f32;
-
-                println!("Found w: {width:?} and h: {height:?}");
-                break;
-            }
+        for (x, y) in [(0, 0), (5, 3), (33, 7)] {
+            let feet = hall.tile_position(x as f32, y as f32);
+            assert_eq!(hall.tile_at(feet), Some((x, y)));
+            assert_eq!(hall.tile_at(feet + Vec2::new(PIXEL_SCALE - 1.0, PIXEL_SCALE - 1.0)), Some((x, y)));
         }
+        assert_eq!(hall.tile_at(Vec2::new(719.0, 200.0)), None);
+        assert_eq!(hall.tile_at(Vec2::new(800.0, 96.0 + 8.0 * 48.0)), None);
     }
-    
-    let area = Rect::new(
-        room_location.translation.x as f64, 
-        room_location.translation.y as f64, 
-        room_location.translation.x as f64 + (width * PIXEL_SCALE) as f64,
-        room_location.translation.y as f64 + (height * PIXEL_SCALE) as f64
-    );
-    println!("Creating Room Area : {:?}", area.clone());
-    area
 
-    
+    #[test]
+    fn room_files_can_hold_exits() {
+        let hall = create_room(Path::new(HALL)).unwrap();
+        let exits: Vec<&Collider> = hall.colliders.iter().filter(|collider| collider.style == ColliderType::ChangeRoom).collect();
+
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0].destination, Some(Destination { level: 1, room: "_0_0".to_string(), spawn: Vec2::new(2.0, 4.0) }));
+        assert_eq!(exits[0].transform.translation.truncate(), tile_top_left(&hall.location, &hall.area, 33.0, 3.0));
+        assert_eq!(exits[0].transform.scale.truncate(), Vec2::new(PIXEL_SCALE, 2.0 * PIXEL_SCALE));
+    }
 }
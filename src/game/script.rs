@@ -0,0 +1,446 @@
+use std::fmt;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::cutscene::CutsceneRequest;
+use super::dialogue::DialogueRequest;
+use super::interaction::InteractionEvent;
+use super::inventory::Inventory;
+use super::transition::{self, Destination};
+use super::{Player, Shadow};
+use super::world_state::{FlagValue, WorldState};
+
+///One parsed line of an Interactable's `action` list
+///
+/// `say "Hello there"`, `dialogue mom_intro`, `give key`, `take key`, `set_flag drawer_open`, `clear_flag drawer_open`,
+/// `set weather rain`, `add times_talked 1`, `teleport L1 _8_9 3 4`, `play_sound door.ogg`, `cutscene intro`
+/// and `if <condition> then <any other command>`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ActionCommand {
+    Say(String),
+    Dialogue(String),
+    Give(String),
+    Take(String),
+    SetFlag(String),
+    ClearFlag(String),
+    Set(String, FlagValue),
+    Add(String, i64),
+    Teleport(Destination),
+    PlaySound(String),
+    Cutscene(String),
+    If { condition: Condition, then: Box<ActionCommand> },
+}
+
+impl TryFrom<String> for ActionCommand {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        parse_action(&text)
+    }
+}
+
+///Something that is either true or not about the WorldState or what the player carries,
+/// `flag x`, `not flag x`, a comparison like `times_talked >= 2` and `weather == rain`, `has key` or `not has key`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Condition {
+    Flag(String),
+    NotFlag(String),
+    Compare { name: String, op: CompareOp, value: FlagValue },
+    Has(String),
+    NotHas(String),
+}
+
+impl Condition {
+    ///`inventory` is None when there is no player to carry anything
+    pub fn holds(&self, world_state: &WorldState, inventory: Option<&Inventory>) -> bool {
+        let has = |item: &str| inventory.is_some_and(|inventory| inventory.count(item) > 0);
+
+        match self {
+            Condition::Flag(name) => world_state.is_set(name),
+            Condition::NotFlag(name) => !world_state.is_set(name),
+            Condition::Compare { name, op, value } => world_state.compare(name, *op, value),
+            Condition::Has(item) => has(item),
+            Condition::NotHas(item) => !has(item),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        parse_condition(&text)
+    }
+}
+
+///The parsed actions of an Interactable, run in order every time it is interacted with
+#[derive(Component, Clone, Debug, Default)]
+pub struct InteractableScript(pub Vec<ActionCommand>);
+
+///An action string that could not be understood
+#[derive(Debug)]
+pub struct ActionParseError {
+    //position of the interactable in its file
+    pub interactable: usize,
+
+    //position of the action in that interactable's action list
+    pub action: usize,
+
+    pub text: String,
+    pub message: String,
+}
+
+impl fmt::Display for ActionParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "interactable {} action {} (`{}`): {}",
+            self.interactable, self.action, self.text, self.message
+        )
+    }
+}
+
+impl std::error::Error for ActionParseError {}
+
+///Parses every action of the interactable at `interactable` (its index in the file), stopping at the first bad one
+pub fn parse_actions(interactable: usize, actions: &[String]) -> Result<InteractableScript, ActionParseError> {
+    let commands = actions
+        .iter()
+        .enumerate()
+        .map(|(index, text)| {
+            parse_action(text).map_err(|message| ActionParseError {
+                interactable,
+                action: index,
+                text: text.clone(),
+                message,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(InteractableScript(commands))
+}
+
+pub fn parse_action(text: &str) -> Result<ActionCommand, String> {
+    parse_tokens(&tokenize(text)?)
+}
+
+fn parse_tokens(tokens: &[String]) -> Result<ActionCommand, String> {
+    let Some((command, args)) = tokens.split_first() else {
+        return Err("empty action".to_string());
+    };
+
+    match command.as_str() {
+        "say" => Ok(ActionCommand::Say(single_arg(command, args)?)),
+        "dialogue" => Ok(ActionCommand::Dialogue(single_arg(command, args)?)),
+        "give" => Ok(ActionCommand::Give(single_arg(command, args)?)),
+        "take" => Ok(ActionCommand::Take(single_arg(command, args)?)),
+        "set_flag" => Ok(ActionCommand::SetFlag(single_arg(command, args)?)),
+        "clear_flag" => Ok(ActionCommand::ClearFlag(single_arg(command, args)?)),
+        "set" => {
+            let [name, value] = args else {
+                return Err("set needs a name and a value, like `set weather rain`".to_string());
+            };
+            Ok(ActionCommand::Set(name.clone(), FlagValue::parse(value)))
+        }
+        "add" => {
+            let [name, amount] = args else {
+                return Err("add needs a name and a number, like `add times_talked 1`".to_string());
+            };
+            let amount = amount.parse().map_err(|_| format!("`{amount}` is not a whole number"))?;
+            Ok(ActionCommand::Add(name.clone(), amount))
+        }
+        "play_sound" => Ok(ActionCommand::PlaySound(single_arg(command, args)?)),
+        "cutscene" => Ok(ActionCommand::Cutscene(single_arg(command, args)?)),
+        "teleport" => {
+            let [level, room, x, y] = args else {
+                return Err("teleport needs a level, room and spawn tile, like `teleport L1 _8_9 3 4`".to_string());
+            };
+
+            let level = level
+                .strip_prefix('L')
+                .unwrap_or(level)
+                .parse()
+                .map_err(|_| format!("`{level}` is not a level"))?;
+            let x: f32 = x.parse().map_err(|_| format!("`{x}` is not a number"))?;
+            let y: f32 = y.parse().map_err(|_| format!("`{y}` is not a number"))?;
+
+            Ok(ActionCommand::Teleport(Destination {
+                level,
+                room: room.clone(),
+                spawn: Vec2::new(x, y),
+            }))
+        }
+        "if" => {
+            let Some(then_at) = args.iter().position(|arg| arg == "then") else {
+                return Err("if needs to look like `if <condition> then <action>`".to_string());
+            };
+
+            Ok(ActionCommand::If {
+                condition: parse_condition_tokens(&args[..then_at])?,
+                then: Box::new(parse_tokens(&args[then_at + 1..])?),
+            })
+        }
+        other => Err(format!("unknown command `{other}`")),
+    }
+}
+
+fn parse_condition_tokens(tokens: &[String]) -> Result<Condition, String> {
+    let words: Vec<&str> = tokens.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["flag", name] => Ok(Condition::Flag(name.to_string())),
+        ["not", "flag", name] => Ok(Condition::NotFlag(name.to_string())),
+        ["has", item] => Ok(Condition::Has(item.to_string())),
+        ["not", "has", item] => Ok(Condition::NotHas(item.to_string())),
+        [name, op, value] => {
+            let op = match *op {
+                "==" => CompareOp::Equal,
+                "!=" => CompareOp::NotEqual,
+                "<" => CompareOp::Less,
+                "<=" => CompareOp::LessOrEqual,
+                ">" => CompareOp::Greater,
+                ">=" => CompareOp::GreaterOrEqual,
+                other => return Err(format!("`{other}` is not a comparison, use one of == != < <= > >=")),
+            };
+            Ok(Condition::Compare { name: name.to_string(), op, value: FlagValue::parse(value) })
+        }
+        _ => Err(format!(
+            "`{}` is not a condition, try `flag <name>`, `not flag <name>`, `<name> >= <value>` or `has <item>`",
+            tokens.join(" ")
+        )),
+    }
+}
+
+///Parses a condition on its own, as used by interactable dependancies
+pub fn parse_condition(text: &str) -> Result<Condition, String> {
+    parse_condition_tokens(&tokenize(text)?)
+}
+
+fn single_arg(command: &str, args: &[String]) -> Result<String, String> {
+    match args {
+        [arg] => Ok(arg.clone()),
+        [] => Err(format!("{command} needs an argument")),
+        _ => Err(format!("{command} takes one argument, put quotes around text with spaces")),
+    }
+}
+
+///Splits on whitespace, except inside double quotes (which can hold \" and \\)
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut token = String::new();
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(escaped) => token.push(escaped),
+                        None => return Err("unfinished escape at the end".to_string()),
+                    },
+                    Some(other) => token.push(other),
+                    None => return Err("missing closing quote".to_string()),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+        }
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+///Everything running an action can touch, so dialogue and interactions share one way of doing it
+#[derive(SystemParam)]
+pub struct ScriptContext<'w, 's> {
+    commands: Commands<'w, 's>,
+    world_state: ResMut<'w, WorldState>,
+    inventories: Query<'w, 's, &'static mut Inventory, (With<Player>, Without<Shadow>)>,
+    asset_server: Res<'w, AssetServer>,
+    dialogue: EventWriter<'w, DialogueRequest>,
+    cutscenes: EventWriter<'w, CutsceneRequest>,
+}
+
+impl ScriptContext<'_, '_> {
+    pub fn run(&mut self, command: &ActionCommand) {
+        match command {
+            ActionCommand::Say(text) => {
+                self.dialogue.send(DialogueRequest::Line(text.clone()));
+            }
+            ActionCommand::Dialogue(name) => {
+                self.dialogue.send(DialogueRequest::Tree(name.clone()));
+            }
+            ActionCommand::Give(item) => match self.inventories.get_single_mut() {
+                Ok(mut inventory) => inventory.add(item),
+                Err(_) => warn!("Can not give {}, there is no player", item),
+            },
+            ActionCommand::Take(item) => {
+                let taken = self.inventories.get_single_mut().is_ok_and(|mut inventory| inventory.remove(item));
+                if !taken {
+                    warn!("Can not take {}, the player does not have one", item);
+                }
+            }
+            ActionCommand::SetFlag(name) => self.world_state.set(name, FlagValue::Bool(true)),
+            ActionCommand::ClearFlag(name) => self.world_state.clear(name),
+            ActionCommand::Set(name, value) => self.world_state.set(name, value.clone()),
+            ActionCommand::Add(name, amount) => self.world_state.add(name, *amount),
+            ActionCommand::Teleport(destination) => {
+                transition::start_room_transition(&mut self.commands, destination.clone());
+            }
+            ActionCommand::PlaySound(file) => {
+                self.commands.spawn(AudioBundle {
+                    source: self.asset_server.load(format!("sounds/{file}")),
+                    settings: PlaybackSettings::DESPAWN,
+                });
+            }
+            ActionCommand::Cutscene(name) => {
+                self.cutscenes.send(CutsceneRequest(name.clone()));
+            }
+            ActionCommand::If { condition, then } => {
+                if self.check(condition) {
+                    self.run(then);
+                }
+            }
+        }
+    }
+
+    pub fn check(&self, condition: &Condition) -> bool {
+        condition.holds(&self.world_state, self.inventories.get_single().ok())
+    }
+}
+
+///Runs the script of everything that was just interacted with
+pub fn run_interaction_scripts(
+    mut events: EventReader<InteractionEvent>,
+    scripts: Query<&InteractableScript>,
+    mut context: ScriptContext,
+) {
+    for event in events.read() {
+        let Ok(script) = scripts.get(event.entity) else {
+            continue;
+        };
+
+        for command in &script.0 {
+            context.run(command);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_command() {
+        assert_eq!(parse_action(r#"say "It's locked.""#), Ok(ActionCommand::Say("It's locked.".to_string())));
+        assert_eq!(parse_action("give key"), Ok(ActionCommand::Give("key".to_string())));
+        assert_eq!(parse_action("take key"), Ok(ActionCommand::Take("key".to_string())));
+        assert_eq!(parse_action("set_flag drawer_open"), Ok(ActionCommand::SetFlag("drawer_open".to_string())));
+        assert_eq!(parse_action("clear_flag drawer_open"), Ok(ActionCommand::ClearFlag("drawer_open".to_string())));
+        assert_eq!(parse_action("set weather rain"), Ok(ActionCommand::Set("weather".to_string(), FlagValue::Text("rain".to_string()))));
+        assert_eq!(parse_action("add times_talked -1"), Ok(ActionCommand::Add("times_talked".to_string(), -1)));
+        assert_eq!(parse_action("play_sound door.ogg"), Ok(ActionCommand::PlaySound("door.ogg".to_string())));
+        assert_eq!(parse_action("cutscene intro"), Ok(ActionCommand::Cutscene("intro".to_string())));
+        assert_eq!(
+            parse_action("teleport L1 _8_9 3 4"),
+            Ok(ActionCommand::Teleport(Destination { level: 1, room: "_8_9".to_string(), spawn: Vec2::new(3.0, 4.0) }))
+        );
+    }
+
+    #[test]
+    fn if_wraps_another_command() {
+        assert_eq!(
+            parse_action(r#"if flag drawer_open then say "Empty.""#),
+            Ok(ActionCommand::If {
+                condition: Condition::Flag("drawer_open".to_string()),
+                then: Box::new(ActionCommand::Say("Empty.".to_string())),
+            })
+        );
+        assert_eq!(
+            parse_action("if not flag lit then dialogue too_dark"),
+            Ok(ActionCommand::If {
+                condition: Condition::NotFlag("lit".to_string()),
+                then: Box::new(ActionCommand::Dialogue("too_dark".to_string())),
+            })
+        );
+        assert_eq!(
+            parse_action("if times_talked >= 3 then set_flag tired"),
+            Ok(ActionCommand::If {
+                condition: Condition::Compare {
+                    name: "times_talked".to_string(),
+                    op: CompareOp::GreaterOrEqual,
+                    value: FlagValue::Int(3),
+                },
+                then: Box::new(ActionCommand::SetFlag("tired".to_string())),
+            })
+        );
+    }
+
+    #[test]
+    fn item_conditions_look_in_the_inventory() {
+        let world_state = WorldState::default();
+        let mut inventory = Inventory::default();
+        let needs_key = parse_condition("has bedroom_key").unwrap();
+
+        assert!(!needs_key.holds(&world_state, Some(&inventory)));
+        inventory.add("bedroom_key");
+        assert!(needs_key.holds(&world_state, Some(&inventory)));
+        assert!(!parse_condition("not has bedroom_key").unwrap().holds(&world_state, Some(&inventory)));
+
+        //nobody to carry it
+        assert!(!needs_key.holds(&world_state, None));
+    }
+
+    #[test]
+    fn quotes_can_hold_escapes() {
+        assert_eq!(parse_action(r#"say "a \"b\" c""#), Ok(ActionCommand::Say(r#"a "b" c"#.to_string())));
+        assert!(parse_action(r#"say "never closed"#).is_err());
+    }
+
+    #[test]
+    fn errors_say_which_interactable_and_action() {
+        let actions = vec!["say hi".to_string(), "teleport L1 _8_9 three 4".to_string()];
+        let err = parse_actions(3, &actions).unwrap_err();
+
+        assert_eq!((err.interactable, err.action), (3, 1));
+        assert_eq!(err.to_string(), "interactable 3 action 1 (`teleport L1 _8_9 three 4`): `three` is not a number");
+    }
+
+    #[test]
+    fn rejects_unknown_and_malformed_commands() {
+        assert!(parse_action("dance").is_err());
+        assert!(parse_action("say").is_err());
+        assert!(parse_action("say two words").is_err());
+        assert!(parse_action("if drawer_open say hi").is_err());
+        assert!(parse_action("if coins ~ 3 then say hi").is_err());
+        assert!(parse_action("add coins lots").is_err());
+        assert!(parse_action("").is_err());
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///A single `<rect>` read out of a collider svg, in svg user units (1 unit = 1 tile)
+/// x and y are already offset by every `translate(..)` above the rect and by the viewBox origin
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub fill: String,
+
+    //every `data-*` attribute on the rect, keyed without the `data-` prefix
+    pub data: BTreeMap<String, String>,
+}
+
+///Everything we care about in a `*_cldr.svg` file
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColliderSvg {
+    pub width: f32,
+    pub height: f32,
+    pub rects: Vec<SvgRect>,
+}
+
+///Things that can go wrong while reading a collider svg
+/// every variant carries the file so the log tells you which room is broken
+#[derive(Debug)]
+pub enum SvgError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Xml {
+        path: PathBuf,
+        source: roxmltree::Error,
+    },
+    NotSvg {
+        path: PathBuf,
+        root: String,
+    },
+    MissingAttribute {
+        path: PathBuf,
+        element: String,
+        attribute: &'static str,
+    },
+    InvalidAttribute {
+        path: PathBuf,
+        element: String,
+        attribute: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgError::Io { path, source } => {
+                write!(f, "could not read {}: {}", path.display(), source)
+            }
+            SvgError::Xml { path, source } => {
+                write!(f, "{} is not valid xml: {}", path.display(), source)
+            }
+            SvgError::NotSvg { path, root } => {
+                write!(f, "{}: expected an <svg> root element, found <{}>", path.display(), root)
+            }
+            SvgError::MissingAttribute { path, element, attribute } => {
+                write!(f, "{}: {} is missing the `{}` attribute", path.display(), element, attribute)
+            }
+            SvgError::InvalidAttribute { path, element, attribute, value } => {
+                write!(f, "{}: {} has an invalid `{}` value: {:?}", path.display(), element, attribute, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SvgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SvgError::Io { source, .. } => Some(source),
+            SvgError::Xml { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+///Reads and parses a collider svg from disk
+pub fn load_collider_svg(path: &Path) -> Result<ColliderSvg, SvgError> {
+    let text = fs::read_to_string(path).map_err(|source| SvgError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    parse_collider_svg(path, &text)
+}
+
+///Writes a collider svg to disk in the same plain format the room files use
+pub fn write_collider_svg(path: &Path, collider_svg: &ColliderSvg) -> Result<(), SvgError> {
+    fs::write(path, collider_svg_text(collider_svg)).map_err(|source| SvgError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+///One `<rect>` per line, top to bottom then left to right, with its `data-*` attributes after the fill
+pub fn collider_svg_text(collider_svg: &ColliderSvg) -> String {
+    let mut rects: Vec<&SvgRect> = collider_svg.rects.iter().collect();
+    rects.sort_by(|a, b| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n");
+    text.push_str(&format!(
+        "<svg version=\"1.1\" width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\" shape-rendering=\"crispEdges\">\n",
+        collider_svg.width, collider_svg.height
+    ));
+
+    for rect in rects {
+        text.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"",
+            rect.x, rect.y, rect.width, rect.height, escape(&rect.fill)
+        ));
+        for (key, value) in &rect.data {
+            text.push_str(&format!(" data-{}=\"{}\"", key, escape(value)));
+        }
+        text.push_str(" />\n");
+    }
+
+    text.push_str("</svg>\n");
+    text
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+///Parses the text of a collider svg, `path` is only used for error messages
+pub fn parse_collider_svg(path: &Path, text: &str) -> Result<ColliderSvg, SvgError> {
+    let doc = roxmltree::Document::parse(text).map_err(|source| SvgError::Xml {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let root = doc.root_element();
+    if root.tag_name().name() != "svg" {
+        return Err(SvgError::NotSvg {
+            path: path.to_path_buf(),
+            root: root.tag_name().name().to_string(),
+        });
+    }
+
+    let parser = Parser { path, doc: &doc };
+
+    //the viewBox is what rect coordinates are measured in, so it wins over width/height
+    //editors like inkscape will happily write width="160mm" next to viewBox="0 0 16 10"
+    let (origin_x, origin_y, width, height) = match root.attribute("viewBox") {
+        Some(view_box) => parser.view_box(root, view_box)?,
+        None => (
+            0.0,
+            0.0,
+            parser.length(root, "width")?,
+            parser.length(root, "height")?,
+        ),
+    };
+
+    let mut rects = Vec::new();
+    parser.walk(root, -origin_x, -origin_y, None, &mut rects)?;
+
+    Ok(ColliderSvg { width, height, rects })
+}
+
+struct Parser<'a, 'input> {
+    path: &'a Path,
+    doc: &'a roxmltree::Document<'input>,
+}
+
+impl Parser<'_, '_> {
+    ///Walks every child of `node`, accumulating translations and the inherited fill as it goes down through `<g>` groups
+    fn walk(
+        &self,
+        node: roxmltree::Node,
+        offset_x: f32,
+        offset_y: f32,
+        inherited_fill: Option<&str>,
+        rects: &mut Vec<SvgRect>,
+    ) -> Result<(), SvgError> {
+        for child in node.children().filter(|n| n.is_element()) {
+            let (dx, dy) = self.translation(child)?;
+            let fill = fill_of(child).or(inherited_fill);
+
+            match child.tag_name().name() {
+                "g" => self.walk(child, offset_x + dx, offset_y + dy, fill, rects)?,
+
+                "rect" => {
+                    //x and y default to 0 in svg, width and height do not
+                    let x = self.number_or(child, "x", 0.0)?;
+                    let y = self.number_or(child, "y", 0.0)?;
+                    let width = self.length(child, "width")?;
+                    let height = self.length(child, "height")?;
+
+                    rects.push(SvgRect {
+                        x: offset_x + dx + x,
+                        y: offset_y + dy + y,
+                        width,
+                        height,
+                        fill: normalize_color(fill.unwrap_or("#000000")),
+                        data: child
+                            .attributes()
+                            .filter_map(|a| Some((a.name().strip_prefix("data-")?.to_string(), a.value().to_string())))
+                            .collect(),
+                    });
+                }
+
+                //metadata, defs, namedview and friends have nothing to do with collision
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    ///Returns the translation of an element's `transform` attribute
+    /// only `translate(..)` is supported, anything else is reported rather than silently ignored
+    fn translation(&self, node: roxmltree::Node) -> Result<(f32, f32), SvgError> {
+        let Some(transform) = node.attribute("transform") else {
+            return Ok((0.0, 0.0));
+        };
+
+        let invalid = || self.invalid(node, "transform", transform);
+
+        let mut total = (0.0, 0.0);
+        let mut rest = transform.trim();
+
+        while !rest.is_empty() {
+            let open = rest.find('(').ok_or_else(invalid)?;
+            let close = rest.find(')').ok_or_else(invalid)?;
+            if close < open || rest[..open].trim() != "translate" {
+                return Err(invalid());
+            }
+
+            let args = parse_numbers(&rest[open + 1..close]).ok_or_else(invalid)?;
+            match args.as_slice() {
+                [x] => total.0 += x,
+                [x, y] => {
+                    total.0 += x;
+                    total.1 += y;
+                }
+                _ => return Err(invalid()),
+            }
+
+            rest = rest[close + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        }
+
+        Ok(total)
+    }
+
+    fn view_box(&self, node: roxmltree::Node, value: &str) -> Result<(f32, f32, f32, f32), SvgError> {
+        match parse_numbers(value).as_deref() {
+            Some([x, y, w, h]) => Ok((*x, *y, *w, *h)),
+            _ => Err(self.invalid(node, "viewBox", value)),
+        }
+    }
+
+    ///Reads a length attribute, allowing a trailing `px` since that is the same as a user unit
+    fn length(&self, node: roxmltree::Node, attribute: &'static str) -> Result<f32, SvgError> {
+        let value = node.attribute(attribute).ok_or_else(|| SvgError::MissingAttribute {
+            path: self.path.to_path_buf(),
+            element: self.describe(node),
+            attribute,
+        })?;
+
+        value
+            .trim()
+            .trim_end_matches("px")
+            .parse::<f32>()
+            .map_err(|_| self.invalid(node, attribute, value))
+    }
+
+    fn number_or(&self, node: roxmltree::Node, attribute: &'static str, default: f32) -> Result<f32, SvgError> {
+        match node.attribute(attribute) {
+            Some(_) => self.length(node, attribute),
+            None => Ok(default),
+        }
+    }
+
+    fn invalid(&self, node: roxmltree::Node, attribute: &'static str, value: &str) -> SvgError {
+        SvgError::InvalidAttribute {
+            path: self.path.to_path_buf(),
+            element: self.describe(node),
+            attribute,
+            value: value.to_string(),
+        }
+    }
+
+    ///Builds something like `<rect id="wall"> at line 12` so a broken element can be found in the file
+    fn describe(&self, node: roxmltree::Node) -> String {
+        let pos = self.doc.text_pos_at(node.range().start);
+        match node.attribute("id") {
+            Some(id) => format!("<{} id=\"{}\"> at line {}", node.tag_name().name(), id, pos.row),
+            None => format!("<{}> at line {}", node.tag_name().name(), pos.row),
+        }
+    }
+}
+
+///Finds the fill of an element, a `fill:` inside `style` beats the `fill` attribute just like in css
+fn fill_of<'a>(node: roxmltree::Node<'a, '_>) -> Option<&'a str> {
+    let from_style = node.attribute("style").and_then(|style| {
+        style.split(';').find_map(|declaration| {
+            let (key, value) = declaration.split_once(':')?;
+            (key.trim() == "fill").then(|| value.trim())
+        })
+    });
+
+    from_style.or_else(|| node.attribute("fill").map(str::trim))
+}
+
+///Upper-cases hex colors and expands the `#rgb` shorthand so `#0f0` and `#00ff00` both become `#00FF00`
+fn normalize_color(color: &str) -> String {
+    let color = color.trim().to_ascii_uppercase();
+
+    match color.strip_prefix('#') {
+        Some(hex) if hex.len() == 3 => {
+            let mut expanded = String::from("#");
+            for c in hex.chars() {
+                expanded.push(c);
+                expanded.push(c);
+            }
+            expanded
+        }
+        _ => color,
+    }
+}
+
+fn parse_numbers(text: &str) -> Option<Vec<f32>> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f32>().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "rooms/_1_1/hall_cldr.svg";
+
+    fn parse(text: &str) -> Result<ColliderSvg, SvgError> {
+        parse_collider_svg(Path::new(PATH), text)
+    }
+
+    fn rect(x: f32, y: f32, fill: &str) -> SvgRect {
+        SvgRect { x, y, width: 1.0, height: 1.0, fill: fill.to_string(), data: BTreeMap::new() }
+    }
+
+    #[test]
+    fn groups_add_up_their_translations() {
+        let collider_svg = parse(r##"<svg viewBox="0 0 16 10">
+            <g transform="translate(2, 1)">
+                <g transform="translate(1) translate(0 3)">
+                    <rect x="1" y="1" width="1" height="1" />
+                </g>
+                <rect width="1" height="1" fill="#00FF00" />
+            </g>
+        </svg>"##).unwrap();
+
+        assert_eq!(collider_svg.rects, vec![rect(4.0, 5.0, "#000000"), rect(2.0, 1.0, "#00FF00")]);
+    }
+
+    #[test]
+    fn style_fill_beats_the_attribute() {
+        let collider_svg = parse(r##"<svg viewBox="0 0 16 10">
+            <g fill="#0000FF">
+                <rect width="1" height="1" />
+                <rect x="1" width="1" height="1" style="stroke:none; fill: #00FF00" fill="#000000" />
+                <rect x="2" width="1" height="1" style="stroke:none" fill="#000000" />
+            </g>
+        </svg>"##).unwrap();
+
+        let fills: Vec<&str> = collider_svg.rects.iter().map(|rect| rect.fill.as_str()).collect();
+        assert_eq!(fills, vec!["#0000FF", "#00FF00", "#000000"]);
+    }
+
+    #[test]
+    fn sizes_can_have_units() {
+        let collider_svg = parse(r#"<svg width="16px" height="10px"><rect x="1px" width="2px" height="1" /></svg>"#).unwrap();
+        assert_eq!((collider_svg.width, collider_svg.height), (16.0, 10.0));
+        assert_eq!(collider_svg.rects[0].width, 2.0);
+
+        //the viewBox is what the rects are in, whatever the width says
+        let collider_svg = parse(r#"<svg width="160mm" height="100mm" viewBox="-1 2 16 10"><rect x="1" y="2" width="1" height="1" /></svg>"#).unwrap();
+        assert_eq!((collider_svg.width, collider_svg.height), (16.0, 10.0));
+        assert_eq!((collider_svg.rects[0].x, collider_svg.rects[0].y), (2.0, 0.0));
+    }
+
+    #[test]
+    fn colors_are_normalized() {
+        assert_eq!(normalize_color("#0f0"), "#00FF00");
+        assert_eq!(normalize_color(" #00ff00 "), "#00FF00");
+        assert_eq!(normalize_color("#abcdef"), "#ABCDEF");
+        assert_eq!(normalize_color("#0F0"), normalize_color("#00ff00"));
+    }
+
+    #[test]
+    fn errors_name_the_file_and_element() {
+        let error = |text: &str| {
+            let error = parse(text).unwrap_err();
+            let message = error.to_string();
+            assert!(message.contains(PATH), "{message}");
+            (error, message)
+        };
+
+        let (xml, _) = error("<svg><rect></svg>");
+        assert!(matches!(xml, SvgError::Xml { .. }));
+
+        let (not_svg, message) = error(r#"<html width="1" height="1" />"#);
+        assert!(matches!(not_svg, SvgError::NotSvg { .. }));
+        assert!(message.contains("<html>"), "{message}");
+
+        let (missing, message) = error("<svg viewBox=\"0 0 4 4\">\n<rect id=\"wall\" width=\"1\" />\n</svg>");
+        assert!(matches!(missing, SvgError::MissingAttribute { attribute: "height", .. }));
+        assert!(message.contains(r#"<rect id="wall"> at line 2"#), "{message}");
+
+        let (invalid, message) = error(r#"<svg viewBox="0 0 4 4"><g transform="rotate(45)"><rect width="1" height="1" /></g></svg>"#);
+        assert!(matches!(invalid, SvgError::InvalidAttribute { attribute: "transform", .. }));
+        assert!(message.contains("<g> at line 1") && message.contains("rotate(45)"), "{message}");
+
+        let (invalid, message) = error(r#"<svg viewBox="0 0 4"></svg>"#);
+        assert!(matches!(invalid, SvgError::InvalidAttribute { attribute: "viewBox", .. }));
+        assert!(message.contains("<svg> at line 1"), "{message}");
+
+        let missing_file = Path::new("rooms/_1_1/missing_cldr.svg");
+        let io = load_collider_svg(missing_file).unwrap_err();
+        assert!(matches!(io, SvgError::Io { .. }));
+        assert!(io.to_string().contains("missing_cldr.svg"));
+    }
+
+    #[test]
+    fn written_files_read_back_the_same() {
+        let path = Path::new("assets/textures/rooms/L1/_0_0/bedroom_cldr.svg");
+        let mut collider_svg = load_collider_svg(path).unwrap();
+        collider_svg.rects.push(SvgRect {
+            x: 3.0,
+            y: 9.0,
+            width: 2.0,
+            height: 1.0,
+            fill: "#00FF00".to_string(),
+            data: BTreeMap::from([("room".to_string(), "\"hall\" & <back>".to_string())]),
+        });
+
+        let mut read_back = parse_collider_svg(path, &collider_svg_text(&collider_svg)).unwrap();
+
+        //written sorted by position, so compare them the same way
+        let order = |a: &SvgRect, b: &SvgRect| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x));
+        collider_svg.rects.sort_by(order);
+        read_back.rects.sort_by(order);
+        assert_eq!(read_back, collider_svg);
+    }
+}
@@ -0,0 +1,189 @@
+use bevy::a11y::accesskit::Rect;
+
+///How many times a move can hit something and slide before we give up on the rest of it
+const MAX_SLIDES: usize = 4;
+
+///Where a moving rect first touches an obstacle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    //fraction of the move (0 to 1) at which the rects first touch
+    pub time: f64,
+
+    //which side of the obstacle was hit, (-1, 0) means the left side
+    pub normal: (f64, f64),
+
+    //the obstacle edge that was hit, so the mover can be snapped exactly against it
+    pub edge: f64,
+}
+
+///Swept AABB test of `moving` travelling by `delta` against a still `obstacle`
+/// Rects that only touch are not a hit, and neither are rects that already overlap (collision_detection pushes those out)
+pub fn sweep_rect(moving: Rect, delta: (f64, f64), obstacle: Rect) -> Option<Hit> {
+    let (entry_x, exit_x) = axis_times(moving.x0, moving.x1, obstacle.x0, obstacle.x1, delta.0)?;
+    let (entry_y, exit_y) = axis_times(moving.y0, moving.y1, obstacle.y0, obstacle.y1, delta.1)?;
+
+    let entry = entry_x.max(entry_y);
+    let exit = exit_x.min(exit_y);
+
+    if entry >= exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+
+    //the later axis to start overlapping is the side we hit, x wins ties so corners slide vertically
+    let hit = if entry_x >= entry_y {
+        if delta.0 > 0.0 {
+            Hit { time: entry, normal: (-1.0, 0.0), edge: obstacle.x0 }
+        } else {
+            Hit { time: entry, normal: (1.0, 0.0), edge: obstacle.x1 }
+        }
+    } else if delta.1 > 0.0 {
+        Hit { time: entry, normal: (0.0, -1.0), edge: obstacle.y0 }
+    } else {
+        Hit { time: entry, normal: (0.0, 1.0), edge: obstacle.y1 }
+    };
+    Some(hit)
+}
+
+///When the moving span starts and stops overlapping the obstacle's span on one axis, as fractions of the move
+/// None means they never overlap on this axis
+fn axis_times(m_min: f64, m_max: f64, o_min: f64, o_max: f64, delta: f64) -> Option<(f64, f64)> {
+    if delta > 0.0 {
+        Some(((o_min - m_max) / delta, (o_max - m_min) / delta))
+    } else if delta < 0.0 {
+        Some(((o_max - m_min) / delta, (o_min - m_max) / delta))
+    } else if m_max <= o_min || m_min >= o_max {
+        None
+    } else {
+        Some((f64::NEG_INFINITY, f64::INFINITY))
+    }
+}
+
+///Moves `start` by `delta`, stopping at the first obstacle in the way and sliding along it with what is left of the move
+/// Returns how far the rect actually moved
+pub fn move_and_slide(start: Rect, delta: (f64, f64), obstacles: &[Rect]) -> (f64, f64) {
+    let mut current = start;
+    let mut remaining = delta;
+
+    for _ in 0..MAX_SLIDES {
+        if remaining == (0.0, 0.0) {
+            break;
+        }
+
+        let first_hit = obstacles
+            .iter()
+            .filter_map(|obstacle| sweep_rect(current, remaining, *obstacle))
+            .min_by(|a, b| a.time.total_cmp(&b.time));
+
+        let Some(hit) = first_hit else {
+            current = offset(current, remaining.0, remaining.1);
+            break;
+        };
+
+        //move up to the contact, snapping flush against the edge so rounding can not leave us inside it
+        let mut moved = (remaining.0 * hit.time, remaining.1 * hit.time);
+        match hit.normal {
+            (nx, _) if nx < 0.0 => moved.0 = hit.edge - current.x1,
+            (nx, _) if nx > 0.0 => moved.0 = hit.edge - current.x0,
+            (_, ny) if ny < 0.0 => moved.1 = hit.edge - current.y1,
+            _ => moved.1 = hit.edge - current.y0,
+        }
+        current = offset(current, moved.0, moved.1);
+
+        //slide: keep the rest of the move, minus the part pushing into the surface
+        remaining = (remaining.0 * (1.0 - hit.time), remaining.1 * (1.0 - hit.time));
+        if hit.normal.0 != 0.0 {
+            remaining.0 = 0.0;
+        } else {
+            remaining.1 = 0.0;
+        }
+    }
+
+    (current.x0 - start.x0, current.y0 - start.y0)
+}
+
+///The area a rect covers over the whole of a move, used to ask the broadphase for everything it could hit
+pub fn swept_bounds(rect: Rect, delta: (f64, f64)) -> Rect {
+    rect.union(offset(rect, delta.0, delta.1))
+}
+
+fn offset(rect: Rect, x: f64, y: f64) -> Rect {
+    Rect::new(rect.x0 + x, rect.y0 + y, rect.x1 + x, rect.y1 + y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a 10x10 player at the origin
+    fn player() -> Rect {
+        Rect::new(0.0, 0.0, 10.0, 10.0)
+    }
+
+    #[test]
+    fn stops_against_a_wall() {
+        let wall = Rect::new(20.0, -50.0, 30.0, 50.0);
+        assert_eq!(move_and_slide(player(), (15.0, 0.0), &[wall]), (10.0, 0.0));
+    }
+
+    #[test]
+    fn does_not_tunnel_through_thin_walls() {
+        //one huge step that would jump clean over a one tile wall
+        let wall = Rect::new(20.0, -50.0, 21.0, 50.0);
+        assert_eq!(move_and_slide(player(), (500.0, 0.0), &[wall]), (10.0, 0.0));
+    }
+
+    #[test]
+    fn slides_along_a_wall_when_moving_diagonally() {
+        let wall = Rect::new(20.0, -50.0, 30.0, 50.0);
+        assert_eq!(move_and_slide(player(), (20.0, 20.0), &[wall]), (10.0, 20.0));
+    }
+
+    #[test]
+    fn stops_in_a_corner_when_moving_diagonally() {
+        let right_wall = Rect::new(20.0, -50.0, 30.0, 50.0);
+        let ceiling = Rect::new(-50.0, 20.0, 50.0, 30.0);
+        assert_eq!(move_and_slide(player(), (40.0, 40.0), &[right_wall, ceiling]), (10.0, 10.0));
+    }
+
+    #[test]
+    fn corner_made_of_separate_tiles_still_stops() {
+        //the same corner built out of unmerged tiles, including the tile where the walls meet
+        let tiles: Vec<Rect> = (0..5)
+            .flat_map(|i| {
+                let i = i as f64 * 10.0;
+                [Rect::new(20.0, i - 20.0, 30.0, i - 10.0), Rect::new(i - 20.0, 20.0, i - 10.0, 30.0)]
+            })
+            .chain([Rect::new(20.0, 20.0, 30.0, 30.0)])
+            .collect();
+
+        assert_eq!(move_and_slide(player(), (40.0, 40.0), &tiles), (10.0, 10.0));
+    }
+
+    #[test]
+    fn sliding_over_tile_seams_does_not_snag() {
+        //floor made of tiles, the player is resting on it and walks along
+        let floor: Vec<Rect> = (-5..5).map(|i| Rect::new(i as f64 * 10.0, -10.0, i as f64 * 10.0 + 10.0, 0.0)).collect();
+        assert_eq!(move_and_slide(player(), (25.0, -5.0), &floor), (25.0, 0.0));
+    }
+
+    #[test]
+    fn diagonal_into_an_outside_corner_slides_past_it() {
+        //exactly hitting the corner of a lone block
+        let block = Rect::new(20.0, 20.0, 30.0, 30.0);
+        assert_eq!(move_and_slide(player(), (20.0, 20.0), &[block]), (10.0, 20.0));
+    }
+
+    #[test]
+    fn can_move_away_from_a_touching_wall() {
+        let wall = Rect::new(10.0, -50.0, 20.0, 50.0);
+        assert_eq!(move_and_slide(player(), (-5.0, 3.0), &[wall]), (-5.0, 3.0));
+        assert_eq!(move_and_slide(player(), (0.0, 7.0), &[wall]), (0.0, 7.0));
+    }
+
+    #[test]
+    fn ignores_things_that_are_out_of_the_way() {
+        let off_to_the_side = Rect::new(20.0, 20.0, 30.0, 30.0);
+        assert_eq!(sweep_rect(player(), (15.0, 0.0), off_to_the_side), None);
+        assert_eq!(move_and_slide(player(), (15.0, 0.0), &[off_to_the_side]), (15.0, 0.0));
+    }
+}
@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+
+use crate::resources::{CurrentLevel, GameState};
+
+use super::broadphase::ColliderGrid;
+use super::{collision_detection, player_rect, Collider, ColliderType, Player, Shadow};
+
+///How long each half (out and back in) of a room transition's fade takes
+const FADE_SECONDS: f32 = 0.4;
+
+pub fn transition_plugin(app: &mut App) {
+    app
+        .add_systems(FixedUpdate, check_room_exits
+            .after(collision_detection)
+            .run_if(in_state(GameState::Running))
+            .run_if(not(resource_exists::<RoomTransition>)))
+
+        .add_systems(Update, run_room_transition.run_if(resource_exists::<RoomTransition>));
+}
+
+///Where a ChangeRoom collider sends the player
+/// read from the `data-level`, `data-room` and `data-spawn` attributes of the collider's rect
+#[derive(Clone, Debug, PartialEq)]
+pub struct Destination {
+    pub level: u32,
+    pub room: String,
+
+    //tile in the target room's collider svg (x right, y down) that the player's feet land on
+    pub spawn: Vec2,
+}
+
+///Left behind for rooms::place_player_at_spawn once the destination level has been asked to load
+#[derive(Resource, Debug)]
+pub struct PendingSpawn(pub Destination);
+
+///Exists while the screen is fading out to, or back in from, a room change
+/// player movement is paused for as long as it is around
+#[derive(Resource, Debug)]
+pub struct RoomTransition {
+    destination: Destination,
+    timer: Timer,
+    fading_in: bool,
+    overlay: Entity,
+}
+
+//Tag component for the black full screen node used for fading
+#[derive(Component)]
+struct FadeOverlay;
+
+///Starts a transition when the player steps onto a ChangeRoom collider that has somewhere to go
+fn check_room_exits(
+    mut commands: Commands,
+    players: Query<&Transform, (With<Player>, Without<Shadow>)>,
+    colliders: Query<&Collider>,
+    collider_grid: Res<ColliderGrid>,
+) {
+    for player_transform in &players {
+        let p_rect = player_rect(player_transform);
+
+        for entity in collider_grid.query(p_rect) {
+            let Ok(collider) = colliders.get(entity) else {
+                continue;
+            };
+            if collider.style != ColliderType::ChangeRoom || p_rect.intersect(collider.rect()).area() == 0.0 {
+                continue;
+            }
+            let Some(destination) = &collider.destination else {
+                continue;
+            };
+
+            info!("Player reached an exit to {:?}", destination);
+            start_room_transition(&mut commands, destination.clone());
+            return;
+        }
+    }
+}
+
+///Starts fading out towards `destination`, run_room_transition takes it from there
+/// the first transition wins, a second teleport in the same action list (or during the fade) is ignored
+pub fn start_room_transition(commands: &mut Commands, destination: Destination) {
+    commands.add(move |world: &mut World| {
+        if let Some(transition) = world.get_resource::<RoomTransition>() {
+            warn!("Already heading to room {}, not going to {:?} as well", transition.destination.room, destination);
+            return;
+        }
+
+        let overlay = world.spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.0).into(),
+                z_index: ZIndex::Global(100),
+                ..default()
+            },
+            FadeOverlay,
+        )).id();
+
+        world.insert_resource(RoomTransition {
+            destination,
+            timer: Timer::from_seconds(FADE_SECONDS, TimerMode::Once),
+            fading_in: false,
+            overlay,
+        });
+    });
+}
+
+///Fades the screen out, swaps the level through GameState::LevelLoading, then fades back in
+fn run_room_transition(
+    mut commands: Commands,
+    mut transition: ResMut<RoomTransition>,
+    mut overlays: Query<&mut BackgroundColor, With<FadeOverlay>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut game_state: ResMut<NextState<GameState>>,
+    state: Res<State<GameState>>,
+    time: Res<Time>,
+) {
+    //hold the screen black until the new level is up and running
+    if transition.fading_in && *state.get() != GameState::Running {
+        return;
+    }
+
+    transition.timer.tick(time.delta());
+
+    let progress = transition.timer.fraction();
+    let alpha = if transition.fading_in { 1.0 - progress } else { progress };
+    if let Ok(mut color) = overlays.get_mut(transition.overlay) {
+        *color = Color::srgba(0.0, 0.0, 0.0, alpha).into();
+    }
+
+    if !transition.timer.finished() {
+        return;
+    }
+
+    if transition.fading_in {
+        commands.entity(transition.overlay).despawn_recursive();
+        commands.remove_resource::<RoomTransition>();
+    } else {
+        info!("Changing to level {} room {}", transition.destination.level, transition.destination.room);
+
+        current_level.0 = transition.destination.level;
+        commands.insert_resource(PendingSpawn(transition.destination.clone()));
+        game_state.set(GameState::LevelLoading);
+
+        transition.fading_in = true;
+        transition.timer.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::world::CommandQueue;
+
+    use super::*;
+
+    fn destination(room: &str) -> Destination {
+        Destination { level: 1, room: room.to_string(), spawn: Vec2::new(2.0, 4.0) }
+    }
+
+    #[test]
+    fn a_second_teleport_does_not_replace_the_first() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        start_room_transition(&mut commands, destination("_8_9"));
+        start_room_transition(&mut commands, destination("_0_0"));
+        queue.apply(&mut world);
+
+        //and neither does one asked for while the screen is already fading
+        let mut commands = Commands::new(&mut queue, &world);
+        start_room_transition(&mut commands, destination("_15_2"));
+        queue.apply(&mut world);
+
+        let overlays = world.query_filtered::<Entity, With<FadeOverlay>>().iter(&world).count();
+        assert_eq!(overlays, 1);
+        assert_eq!(world.resource::<RoomTransition>().destination, destination("_8_9"));
+    }
+}
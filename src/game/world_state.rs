@@ -0,0 +1,240 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::resources::{CurrentLevel, GameState};
+
+use super::interaction::InteractableMemory;
+use super::inventory::Inventory;
+use super::script::CompareOp;
+use super::{Player, Shadow};
+
+///Where the game is saved to, and continued from
+pub const SAVE_PATH: &str = "saves/save.json";
+
+pub fn world_state_plugin(app: &mut App) {
+    app
+        .init_resource::<WorldState>()
+
+        //every level that finishes loading is a checkpoint, rooms streaming in on the way through it are not
+        .add_systems(OnEnter(GameState::LevelLoading), mark_checkpoint)
+        .add_systems(OnEnter(GameState::Running), autosave.run_if(resource_exists::<Checkpoint>));
+}
+
+//Set while a new level loads, the game is saved once it is Running again
+#[derive(Resource)]
+struct Checkpoint;
+
+///A named value in the WorldState
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FlagValue {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl FlagValue {
+    ///Reads a value the way actions write them: true/false, a whole number, or anything else as text
+    pub fn parse(text: &str) -> FlagValue {
+        match text {
+            "true" => FlagValue::Bool(true),
+            "false" => FlagValue::Bool(false),
+            _ => text
+                .parse()
+                .map(FlagValue::Int)
+                .unwrap_or_else(|_| FlagValue::Text(text.to_string())),
+        }
+    }
+
+    ///Whether this counts as set for `flag x`, false, 0 and empty text do not
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            FlagValue::Bool(value) => *value,
+            FlagValue::Int(value) => *value != 0,
+            FlagValue::Text(value) => !value.is_empty(),
+        }
+    }
+
+    ///Numbers compare as numbers, everything else only knows equal or not
+    fn compare(&self, other: &FlagValue) -> Option<Ordering> {
+        match (self, other) {
+            (FlagValue::Int(a), FlagValue::Int(b)) => Some(a.cmp(b)),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FlagValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagValue::Bool(value) => write!(f, "{value}"),
+            FlagValue::Int(value) => write!(f, "{value}"),
+            FlagValue::Text(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
+///Everything the story remembers ("the bedroom drawer was opened", "times talked to mom"), saved with the game
+#[derive(Resource, Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldState(BTreeMap<String, FlagValue>);
+
+impl WorldState {
+    pub fn get(&self, name: &str) -> Option<&FlagValue> {
+        self.0.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: FlagValue) {
+        self.0.insert(name.to_string(), value);
+    }
+
+    pub fn clear(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    ///Adds to a number, anything that was not a number yet counts as 0
+    pub fn add(&mut self, name: &str, amount: i64) {
+        let current = match self.get(name) {
+            Some(FlagValue::Int(value)) => *value,
+            _ => 0,
+        };
+        self.set(name, FlagValue::Int(current.saturating_add(amount)));
+    }
+
+    ///Whether `flag name` holds, false, 0, empty text and missing values do not count
+    pub fn is_set(&self, name: &str) -> bool {
+        self.get(name).is_some_and(FlagValue::is_truthy)
+    }
+
+    pub fn compare(&self, name: &str, op: CompareOp, value: &FlagValue) -> bool {
+        let ordering = self.get(name).and_then(|current| current.compare(value));
+        match op {
+            CompareOp::Equal => ordering == Some(Ordering::Equal),
+            CompareOp::NotEqual => ordering != Some(Ordering::Equal),
+            CompareOp::Less => ordering == Some(Ordering::Less),
+            CompareOp::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Greater => ordering == Some(Ordering::Greater),
+            CompareOp::GreaterOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+///What goes in a save file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub level: u32,
+    pub world_state: WorldState,
+
+    #[serde(default)]
+    pub inventory: Inventory,
+
+    #[serde(default)]
+    pub interactables: InteractableMemory,
+}
+
+impl SaveGame {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file_content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&file_content)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn mark_checkpoint(mut commands: Commands) {
+    commands.insert_resource(Checkpoint);
+}
+
+fn autosave(
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    world_state: Res<WorldState>,
+    interactables: Res<InteractableMemory>,
+    players: Query<&Inventory, (With<Player>, Without<Shadow>)>,
+) {
+    commands.remove_resource::<Checkpoint>();
+
+    let save = SaveGame {
+        level: current_level.0,
+        world_state: world_state.clone(),
+        inventory: players.get_single().cloned().unwrap_or_default(),
+        interactables: interactables.clone(),
+    };
+
+    match save.save(SAVE_PATH) {
+        Ok(()) => info!("Saved the game to {}", SAVE_PATH),
+        Err(err) => error!("Could not save the game to {}: {}", SAVE_PATH, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_compare_by_type() {
+        let mut world = WorldState::default();
+        world.add("talked_to_mom", 2);
+        world.set("weather", FlagValue::parse("rain"));
+
+        assert!(world.is_set("talked_to_mom"));
+        assert!(world.compare("talked_to_mom", CompareOp::GreaterOrEqual, &FlagValue::Int(2)));
+        assert!(!world.compare("talked_to_mom", CompareOp::Less, &FlagValue::Int(2)));
+        assert!(world.compare("weather", CompareOp::Equal, &FlagValue::Text("rain".to_string())));
+
+        //text has no order, and missing values only ever differ
+        assert!(!world.compare("weather", CompareOp::Greater, &FlagValue::Text("a".to_string())));
+        assert!(world.compare("missing", CompareOp::NotEqual, &FlagValue::Int(0)));
+    }
+
+    #[test]
+    fn falsy_values_are_not_set() {
+        let mut world = WorldState::default();
+        world.set("door_open", FlagValue::Bool(false));
+        world.set("coins", FlagValue::Int(0));
+
+        assert!(!world.is_set("door_open"));
+        assert!(!world.is_set("coins"));
+        assert!(!world.is_set("never_set"));
+    }
+
+    #[test]
+    fn counters_stop_at_the_limits() {
+        let mut world = WorldState::default();
+        world.set("coins", FlagValue::Int(i64::MAX - 1));
+        world.add("coins", 5);
+        world.add("debt", i64::MIN);
+        world.add("debt", -1);
+
+        assert_eq!(world.get("coins"), Some(&FlagValue::Int(i64::MAX)));
+        assert_eq!(world.get("debt"), Some(&FlagValue::Int(i64::MIN)));
+    }
+
+    #[test]
+    fn saves_round_trip_through_json() {
+        let mut world_state = WorldState::default();
+        world_state.set("drawer_open", FlagValue::Bool(true));
+        world_state.set("coins", FlagValue::Int(3));
+        world_state.set("name", FlagValue::Text("Harken".to_string()));
+
+        let mut inventory = Inventory::default();
+        inventory.add("bedroom_key");
+
+        let save = SaveGame { level: 2, world_state, inventory, interactables: InteractableMemory::default() };
+        let json = serde_json::to_string(&save).unwrap();
+        assert_eq!(serde_json::from_str::<SaveGame>(&json).unwrap(), save);
+    }
+}
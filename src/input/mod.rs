@@ -0,0 +1,387 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+///Where the player's key and button bindings are kept, next to their save rather than in the game's assets
+pub const BINDINGS_PATH: &str = "saves/bindings.json";
+
+pub fn input_plugin(app: &mut App) {
+    let bindings = match InputBindings::load(BINDINGS_PATH) {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            warn!("Could not load {}, using default bindings: {}", BINDINGS_PATH, err);
+            InputBindings::default()
+        }
+    };
+
+    app
+        .insert_resource(bindings)
+        .init_resource::<ActionState>()
+        .add_systems(PreUpdate, (
+            update_action_state,
+            capture_rebind.run_if(resource_exists::<PendingRebind>),
+        ).chain().after(InputSystem));
+}
+
+///Everything the player can do, gameplay asks about these instead of about keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum GameAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Sneak,
+    Interact,
+    Inventory,
+    DevPlaceInteractable,
+    DevSaveInteractables,
+    DevColliderEditor,
+    DevSaveColliders,
+    DevInteractableEditor,
+    DevToggleColliders,
+    DevToggleRooms,
+    DevTogglePlayer,
+    DevToggleInteractables,
+}
+
+impl GameAction {
+    pub const ALL: [GameAction; 17] = [
+        GameAction::MoveUp,
+        GameAction::MoveDown,
+        GameAction::MoveLeft,
+        GameAction::MoveRight,
+        GameAction::Sprint,
+        GameAction::Sneak,
+        GameAction::Interact,
+        GameAction::Inventory,
+        GameAction::DevPlaceInteractable,
+        GameAction::DevSaveInteractables,
+        GameAction::DevColliderEditor,
+        GameAction::DevSaveColliders,
+        GameAction::DevInteractableEditor,
+        GameAction::DevToggleColliders,
+        GameAction::DevToggleRooms,
+        GameAction::DevTogglePlayer,
+        GameAction::DevToggleInteractables,
+    ];
+}
+
+///A physical input that can trigger an action
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Gamepad(GamepadButtonType),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key:?}"),
+            Binding::Gamepad(button) => write!(f, "Pad {button:?}"),
+        }
+    }
+}
+
+impl Binding {
+    ///A short name to show in game, `E` rather than `KeyE`
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => {
+                let name = format!("{key:?}");
+                name.strip_prefix("Key")
+                    .or_else(|| name.strip_prefix("Digit"))
+                    .unwrap_or(&name)
+                    .to_string()
+            }
+            Binding::Gamepad(button) => format!("{button:?}"),
+        }
+    }
+}
+
+///Left stick tilt past this (0 to 1) also counts as holding that direction's move action, which is what drives menus
+const STICK_PRESS_THRESHOLD: f32 = 0.5;
+
+///Which inputs trigger which action, saved to and loaded from BINDINGS_PATH
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    actions: BTreeMap<GameAction, Vec<Binding>>,
+
+    //how far (0 to 1) the left stick has to be pushed before it moves the player at all
+    #[serde(default = "default_stick_deadzone")]
+    stick_deadzone: f32,
+}
+
+fn default_stick_deadzone() -> f32 {
+    0.2
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::{Gamepad, Key};
+        use GamepadButtonType as Pad;
+
+        let actions = BTreeMap::from([
+            (GameAction::MoveUp, vec![Key(KeyCode::ArrowUp), Gamepad(Pad::DPadUp)]),
+            (GameAction::MoveDown, vec![Key(KeyCode::ArrowDown), Gamepad(Pad::DPadDown)]),
+            (GameAction::MoveLeft, vec![Key(KeyCode::ArrowLeft), Gamepad(Pad::DPadLeft)]),
+            (GameAction::MoveRight, vec![Key(KeyCode::ArrowRight), Gamepad(Pad::DPadRight)]),
+            (GameAction::Sprint, vec![Key(KeyCode::ShiftLeft), Gamepad(Pad::RightTrigger)]),
+            (GameAction::Sneak, vec![Key(KeyCode::ControlLeft), Gamepad(Pad::LeftTrigger)]),
+            (GameAction::Interact, vec![Key(KeyCode::KeyE), Gamepad(Pad::South)]),
+            (GameAction::Inventory, vec![Key(KeyCode::Tab), Gamepad(Pad::North)]),
+            (GameAction::DevPlaceInteractable, vec![Key(KeyCode::KeyI)]),
+            (GameAction::DevSaveInteractables, vec![Key(KeyCode::KeyP)]),
+            (GameAction::DevColliderEditor, vec![Key(KeyCode::KeyC)]),
+            (GameAction::DevSaveColliders, vec![Key(KeyCode::KeyO)]),
+            (GameAction::DevInteractableEditor, vec![Key(KeyCode::KeyU)]),
+            (GameAction::DevToggleColliders, vec![Key(KeyCode::F1)]),
+            (GameAction::DevToggleRooms, vec![Key(KeyCode::F2)]),
+            (GameAction::DevTogglePlayer, vec![Key(KeyCode::F3)]),
+            (GameAction::DevToggleInteractables, vec![Key(KeyCode::F4)]),
+        ]);
+
+        InputBindings {
+            actions,
+            stick_deadzone: default_stick_deadzone(),
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file_content = fs::read_to_string(path)?;
+        let mut bindings: InputBindings = serde_json::from_str(&file_content)?;
+
+        //actions added since the file was written get their default bindings
+        for (action, defaults) in InputBindings::default().actions {
+            bindings.actions.entry(action).or_insert(defaults);
+        }
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn bindings_for(&self, action: GameAction) -> &[Binding] {
+        self.actions.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    ///Applies the radial deadzone to a raw stick position, rescaling so movement still starts from zero at its edge
+    pub fn apply_deadzone(&self, stick: Vec2) -> Vec2 {
+        let length = stick.length();
+        if length <= self.stick_deadzone || self.stick_deadzone >= 1.0 {
+            return Vec2::ZERO;
+        }
+
+        let scaled = ((length - self.stick_deadzone) / (1.0 - self.stick_deadzone)).min(1.0);
+        stick / length * scaled
+    }
+
+    ///Binds `binding` to `action`, replacing the action's old binding on the same device (keyboard or gamepad)
+    /// the binding is taken off any other action so one input never does two things
+    pub fn rebind(&mut self, action: GameAction, binding: Binding) {
+        let same_device = |a: &Binding, b: &Binding| matches!((a, b), (Binding::Key(_), Binding::Key(_)) | (Binding::Gamepad(_), Binding::Gamepad(_)));
+
+        for bindings in self.actions.values_mut() {
+            bindings.retain(|b| *b != binding);
+        }
+
+        let bindings = self.actions.entry(action).or_default();
+        bindings.retain(|b| !same_device(b, &binding));
+        bindings.push(binding);
+    }
+}
+
+///The state of every action this frame, updated from the bindings before Update runs
+#[derive(Resource, Debug, Default)]
+pub struct ActionState {
+    pressed: HashSet<GameAction>,
+    just_pressed: HashSet<GameAction>,
+
+    //left stick of whichever gamepad is pushed furthest, after the deadzone
+    stick: Vec2,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: GameAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: GameAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    ///Which way the player wants to move, each axis between -1 and 1
+    /// an analog stick gives partial values, keys and the d-pad always give whole ones
+    pub fn movement(&self) -> Vec2 {
+        if self.stick != Vec2::ZERO {
+            return self.stick;
+        }
+
+        let mut direction = Vec2::ZERO;
+        if self.pressed(GameAction::MoveUp) {
+            direction.y += 1.0;
+        }
+        if self.pressed(GameAction::MoveDown) {
+            direction.y -= 1.0;
+        }
+        if self.pressed(GameAction::MoveRight) {
+            direction.x += 1.0;
+        }
+        if self.pressed(GameAction::MoveLeft) {
+            direction.x -= 1.0;
+        }
+        direction
+    }
+}
+
+///Set this to bind the next key or gamepad button pressed to an action, the new bindings are saved straight away
+#[derive(Resource, Debug)]
+pub struct PendingRebind(pub GameAction);
+
+///Exists while something is being typed, so the keyboard does not also walk the player around or open menus
+#[derive(Resource, Debug)]
+pub struct TypingText;
+
+fn binding_pressed(binding: &Binding, keys: &ButtonInput<KeyCode>, buttons: &ButtonInput<GamepadButton>, gamepads: &Gamepads) -> bool {
+    match binding {
+        Binding::Key(key) => keys.pressed(*key),
+        Binding::Gamepad(button_type) => gamepads
+            .iter()
+            .any(|gamepad| buttons.pressed(GamepadButton::new(gamepad, *button_type))),
+    }
+}
+
+fn update_action_state(
+    bindings: Res<InputBindings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    typing: Option<Res<TypingText>>,
+    mut state: ResMut<ActionState>,
+) {
+    let was_pressed = std::mem::take(&mut state.pressed);
+    state.just_pressed.clear();
+
+    state.stick = gamepads
+        .iter()
+        .map(|gamepad| {
+            let x = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0);
+            let y = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.0);
+            bindings.apply_deadzone(Vec2::new(x, y))
+        })
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .unwrap_or(Vec2::ZERO);
+
+    for action in GameAction::ALL {
+        let from_stick = match action {
+            GameAction::MoveUp => state.stick.y > STICK_PRESS_THRESHOLD,
+            GameAction::MoveDown => state.stick.y < -STICK_PRESS_THRESHOLD,
+            GameAction::MoveRight => state.stick.x > STICK_PRESS_THRESHOLD,
+            GameAction::MoveLeft => state.stick.x < -STICK_PRESS_THRESHOLD,
+            _ => false,
+        };
+
+        let pressed = from_stick || bindings
+            .bindings_for(action)
+            .iter()
+            .filter(|binding| typing.is_none() || !matches!(binding, Binding::Key(_)))
+            .any(|binding| binding_pressed(binding, &keys, &buttons, &gamepads));
+
+        if pressed {
+            state.pressed.insert(action);
+            if !was_pressed.contains(&action) {
+                state.just_pressed.insert(action);
+            }
+        }
+    }
+}
+
+fn capture_rebind(
+    mut commands: Commands,
+    pending: Res<PendingRebind>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let pressed = keys
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| buttons.get_just_pressed().next().map(|button| Binding::Gamepad(button.button_type)));
+
+    let Some(binding) = pressed else {
+        return;
+    };
+
+    info!("Binding {} to {:?}", binding, pending.0);
+    bindings.rebind(pending.0, binding);
+    commands.remove_resource::<PendingRebind>();
+
+    if let Err(err) = bindings.save(BINDINGS_PATH) {
+        error!("Could not save bindings to {}: {}", BINDINGS_PATH, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_replaces_the_same_device_only() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(GameAction::Interact, Binding::Key(KeyCode::KeyF));
+
+        assert_eq!(
+            bindings.bindings_for(GameAction::Interact),
+            &[Binding::Gamepad(GamepadButtonType::South), Binding::Key(KeyCode::KeyF)]
+        );
+    }
+
+    #[test]
+    fn rebinding_steals_the_input_from_other_actions() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(GameAction::Interact, Binding::Key(KeyCode::ArrowUp));
+
+        assert_eq!(bindings.bindings_for(GameAction::MoveUp), &[Binding::Gamepad(GamepadButtonType::DPadUp)]);
+    }
+
+    #[test]
+    fn labels_are_short() {
+        assert_eq!(Binding::Key(KeyCode::KeyE).label(), "E");
+        assert_eq!(Binding::Key(KeyCode::Digit1).label(), "1");
+        assert_eq!(Binding::Key(KeyCode::Tab).label(), "Tab");
+    }
+
+    #[test]
+    fn small_stick_movements_are_ignored() {
+        let bindings = InputBindings::default();
+
+        assert_eq!(bindings.apply_deadzone(Vec2::new(0.1, 0.1)), Vec2::ZERO);
+        assert_eq!(bindings.apply_deadzone(Vec2::new(1.0, 0.0)), Vec2::new(1.0, 0.0));
+
+        //just past the deadzone starts slow instead of jumping straight to its size
+        let barely = bindings.apply_deadzone(Vec2::new(0.0, -0.25));
+        assert!(barely.y < 0.0 && barely.y > -0.1);
+    }
+
+    #[test]
+    fn bindings_round_trip_through_json() {
+        let bindings = InputBindings::default();
+        let json = serde_json::to_string(&bindings).unwrap();
+        assert_eq!(serde_json::from_str::<InputBindings>(&json).unwrap(), bindings);
+    }
+}
@@ -1,3 +1,6 @@
+//bevy queries and their filters trip this lint constantly
+#![allow(clippy::type_complexity)]
+
 use bevy::{
     prelude::*,
     diagnostic::FrameTimeDiagnosticsPlugin,
@@ -6,6 +9,7 @@ use bevy::{
 
 
 mod resources;
+mod input;
 mod menu;
 mod game;
 mod dev_tools;
@@ -42,6 +46,7 @@ fn main() {
         .insert_resource(resources::DebugMode(true))
         .insert_resource(resources::DevMode(true))
 
+        .add_plugins(input::input_plugin)
         .add_plugins(dev_tools::dev_tools)
 
         .insert_resource(ClearColor(Color::srgba(0.0, 0.0, 0.0, 0.0)))
@@ -1,5 +1,9 @@
 use bevy::{app::AppExit, prelude::*};
 use super::{despawn_screen, resources::*};
+use crate::game::interaction::InteractableMemory;
+use crate::game::inventory::CarriedInventory;
+use crate::game::world_state::{SaveGame, WorldState, SAVE_PATH};
+use crate::input::{ActionState, GameAction, InputBindings, PendingRebind};
 
 const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
 
@@ -11,6 +15,7 @@ enum MainMenuState {
     SettingMain,
     SettingsDisplay,
     SettingsSound,
+    SettingsControls,
     Disabled,
 }
 
@@ -58,10 +63,29 @@ pub fn main_menu_plugin(app: &mut App) {
             despawn_screen::<OnSoundSettingsMenu>,
         )
 
+        //controls
+        .add_systems(OnEnter(MainMenuState::SettingsControls), controls_settings_menu_setup)
+        .add_systems(
+            Update,
+            (
+                rebind_button_action,
+                refresh_binding_labels.run_if(resource_changed::<InputBindings>),
+            ).run_if(in_state(MainMenuState::SettingsControls)),
+        )
+        .add_systems(
+            OnExit(MainMenuState::SettingsControls),
+            despawn_screen::<OnControlsSettingsMenu>,
+        )
+
         //systems common to all settings menus
         .add_systems(
             Update,
-            (menu_action, button_system).run_if(in_state(GameState::MainMenu))
+            (
+                gamepad_menu_navigation.run_if(not(resource_exists::<PendingRebind>)),
+                menu_action,
+                button_system,
+                highlight_menu_focus,
+            ).chain().run_if(in_state(GameState::MainMenu))
         );
 
     }
@@ -82,6 +106,18 @@ struct OnDisplaySettingsMenu;
 #[derive(Component)]
 struct OnSoundSettingsMenu;
 
+// Tag component used to tag entities added on the controls settings menu screen
+#[derive(Component)]
+struct OnControlsSettingsMenu;
+
+// Button that rebinds an action when pressed
+#[derive(Component)]
+struct RebindButton(GameAction);
+
+// Text showing what an action is currently bound to
+#[derive(Component)]
+struct BindingLabel(GameAction);
+
 //colors of buttons in different states
 const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
@@ -90,6 +126,10 @@ const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
 
 const CRIMSON : Color = Color::srgb(0.863, 0.078, 0.235);
 
+// Tag component for the button the gamepad (or arrow keys) has moved onto
+#[derive(Component)]
+struct MenuFocus;
+
 // Tag component used to mark which setting is currently selected
 #[derive(Component)]
 struct SelectedOption;
@@ -102,6 +142,7 @@ enum MenuButtonAction {
     Settings,
     SettingsDisplay,
     SettingsSound,
+    SettingsControls,
     BackToMainMenu,
     BackToSettings,
     Quit,
@@ -152,6 +193,7 @@ fn settings_menu_setup(mut commands: Commands) {
                     for (action, text) in [
                         (MenuButtonAction::SettingsDisplay, "Display"),
                         (MenuButtonAction::SettingsSound, "Sound"),
+                        (MenuButtonAction::SettingsControls, "Controls"),
                         (MenuButtonAction::BackToMainMenu, "Back"),
                     ] {
                         parent
@@ -367,6 +409,150 @@ fn display_settings_menu_setup(mut commands: Commands, display_quality: Res<Disp
         });
 }
 
+//Display and formatting for SettingsControls Menu
+fn controls_settings_menu_setup(mut commands: Commands, bindings: Res<InputBindings>) {
+    let row_text_style = TextStyle {
+        font_size: 24.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font_size: 40.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnControlsSettingsMenu,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    // One row per action, clicking the binding waits for the next key or button
+                    for action in GameAction::ALL {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(560.0),
+                                    justify_content: JustifyContent::SpaceBetween,
+                                    align_items: AlignItems::Center,
+                                    margin: UiRect::horizontal(Val::Px(20.0)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    format!("{action:?}"),
+                                    row_text_style.clone(),
+                                ));
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(260.0),
+                                                height: Val::Px(40.0),
+                                                margin: UiRect::vertical(Val::Px(4.0)),
+                                                justify_content: JustifyContent::Center,
+                                                align_items: AlignItems::Center,
+                                                ..default()
+                                            },
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        RebindButton(action),
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn((
+                                            TextBundle::from_section(
+                                                binding_text(&bindings, action),
+                                                row_text_style.clone(),
+                                            ),
+                                            BindingLabel(action),
+                                        ));
+                                    });
+                            });
+                    }
+                    // Display the back button to return to the settings screen
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(200.0),
+                                    height: Val::Px(65.0),
+                                    margin: UiRect::all(Val::Px(20.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::BackToSettings,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("Back", button_text_style));
+                        });
+                });
+        });
+}
+
+fn binding_text(bindings: &InputBindings, action: GameAction) -> String {
+    let names: Vec<String> = bindings.bindings_for(action).iter().map(|b| b.to_string()).collect();
+    if names.is_empty() {
+        "Unbound".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+fn rebind_button_action(
+    interaction_query: Query<(&Interaction, &RebindButton), (Changed<Interaction>, With<Button>)>,
+    mut labels: Query<(&mut Text, &BindingLabel)>,
+    mut commands: Commands,
+) {
+    for (interaction, rebind_button) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            commands.insert_resource(PendingRebind(rebind_button.0));
+
+            for (mut text, label) in &mut labels {
+                if label.0 == rebind_button.0 {
+                    text.sections[0].value = "Press a key...".to_string();
+                }
+            }
+        }
+    }
+}
+
+fn refresh_binding_labels(
+    bindings: Res<InputBindings>,
+    mut labels: Query<(&mut Text, &BindingLabel)>,
+) {
+    for (mut text, label) in &mut labels {
+        text.sections[0].value = binding_text(&bindings, label.0);
+    }
+}
+
 fn alt_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 
     let background_texture = asset_server.load("textures/rooms/interaction_boundary.png");
@@ -539,7 +725,8 @@ fn menu_action(
     mut app_exit_events: EventWriter<AppExit>,
     mut menu_state: ResMut<NextState<MainMenuState>>,
     mut game_state: ResMut<NextState<GameState>>,
-    mut current_level: ResMut<CurrentLevel>
+    mut current_level: ResMut<CurrentLevel>,
+    mut commands: Commands,
 ){
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
@@ -548,9 +735,24 @@ fn menu_action(
                     game_state.set(GameState::LevelLoading);
                     menu_state.set(MainMenuState::Disabled);
                     current_level.0 = 1;
+                    commands.insert_resource(WorldState::default());
+                    commands.insert_resource(CarriedInventory::default());
+                    commands.insert_resource(InteractableMemory::default());
                 }
                 MenuButtonAction::Continue => {
-                    game_state.set(GameState::Loading);
+                    match SaveGame::load(SAVE_PATH) {
+                        Ok(save) => {
+                            current_level.0 = save.level;
+                            commands.insert_resource(save.world_state);
+                            commands.insert_resource(CarriedInventory(save.inventory));
+                            commands.insert_resource(save.interactables);
+                            game_state.set(GameState::LevelLoading);
+                        }
+                        Err(err) => {
+                            warn!("Could not load {}: {}", SAVE_PATH, err);
+                            game_state.set(GameState::Loading);
+                        }
+                    }
                     menu_state.set(MainMenuState::Disabled);
                 }
                 MenuButtonAction::Settings => {
@@ -562,6 +764,9 @@ fn menu_action(
                 MenuButtonAction::SettingsSound => {
                     menu_state.set(MainMenuState::SettingsSound);
                 }
+                MenuButtonAction::SettingsControls => {
+                    menu_state.set(MainMenuState::SettingsControls);
+                }
                 MenuButtonAction::BackToMainMenu => {
                     menu_state.set(MainMenuState::Main);
                 }
@@ -591,6 +796,81 @@ fn button_system(
         }
     }
 }
+///Moves MenuFocus between buttons with the move actions (d-pad, left stick or arrow keys) and presses it with Interact
+/// buttons are ordered top to bottom then left to right, up/left go back through them and down/right go forward
+fn gamepad_menu_navigation(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut buttons: Query<(Entity, &GlobalTransform, &mut Interaction, Has<MenuFocus>), With<Button>>,
+    mut pressed_last_frame: Local<Option<Entity>>,
+) {
+    //a mouse click gets let go of by bevy, a pretend one has to be let go of by us
+    if let Some(entity) = pressed_last_frame.take() {
+        if let Ok((_, _, mut interaction, _)) = buttons.get_mut(entity) {
+            interaction.set_if_neq(Interaction::None);
+        }
+    }
+
+    let mut order: Vec<(Entity, Vec3)> = buttons
+        .iter()
+        .map(|(entity, transform, _, _)| (entity, transform.translation()))
+        .collect();
+    order.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    let focused = order.iter().position(|(entity, _)| buttons.get(*entity).is_ok_and(|(_, _, _, focus)| focus));
+
+    let step: i32 = if actions.just_pressed(GameAction::MoveUp) || actions.just_pressed(GameAction::MoveLeft) {
+        -1
+    } else if actions.just_pressed(GameAction::MoveDown) || actions.just_pressed(GameAction::MoveRight) {
+        1
+    } else {
+        0
+    };
+
+    if step != 0 && !order.is_empty() {
+        //nothing focused yet (a new screen was just opened) starts from the first button
+        let next = match focused {
+            Some(index) => (index as i32 + step).rem_euclid(order.len() as i32) as usize,
+            None => 0,
+        };
+
+        if let Some(index) = focused {
+            commands.entity(order[index].0).remove::<MenuFocus>();
+        }
+        commands.entity(order[next].0).insert(MenuFocus);
+        return;
+    }
+
+    if actions.just_pressed(GameAction::Interact) {
+        if let Some(index) = focused {
+            let entity = order[index].0;
+            if let Ok((_, _, mut interaction, _)) = buttons.get_mut(entity) {
+                *interaction = Interaction::Pressed;
+                *pressed_last_frame = Some(entity);
+            }
+        }
+    }
+}
+
+///Colors the focused button like a hovered one, and puts buttons back the way button_system would once they lose focus
+fn highlight_menu_focus(
+    mut focused: Query<(&Interaction, &mut BackgroundColor, Has<SelectedOption>), With<MenuFocus>>,
+    mut others: Query<(&Interaction, &mut BackgroundColor, Has<SelectedOption>), Without<MenuFocus>>,
+    mut unfocused: RemovedComponents<MenuFocus>,
+) {
+    for (interaction, mut color, selected) in &mut focused {
+        if *interaction == Interaction::None {
+            *color = if selected { HOVERED_PRESSED_BUTTON.into() } else { HOVERED_BUTTON.into() };
+        }
+    }
+
+    for entity in unfocused.read() {
+        if let Ok((Interaction::None, mut color, selected)) = others.get_mut(entity) {
+            *color = if selected { PRESSED_BUTTON.into() } else { NORMAL_BUTTON.into() };
+        }
+    }
+}
+
 #[derive(Component)]
 struct OnSplashScreen;
 
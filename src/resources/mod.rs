@@ -10,6 +10,15 @@ pub enum GameState {
     //Dead,
 }
 
+///What the game is doing while it is Running, player control is taken away during a Cutscene
+#[derive(SubStates, Clone, Copy, Default, Eq, PartialEq, Debug, Hash)]
+#[source(GameState = GameState::Running)]
+pub enum PlayState {
+    #[default]
+    Playing,
+    Cutscene,
+}
+
 
 #[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
 pub enum DisplayQuality {